@@ -1,59 +1,84 @@
-use chrono::Utc;
-use color_eyre::Result;
-use color_eyre::eyre::OptionExt;
-use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
+use clap::Args;
+use rss::{Channel, ChannelBuilder, GuidBuilder, ItemBuilder};
 use scraper::{Html, Selector};
 use tracing::error_span;
 
+use crate::error::{OptionExt, Result};
+use crate::http::{self, Modified};
+use crate::scraper::{Feed, Fetched, Scraper};
+
 const HOST: &str = "https://archiveofourown.org";
 
-pub fn get(series: String) -> Result<()> {
-    let client = reqwest::blocking::Client::new();
+#[derive(Debug, Clone, Args)]
+pub struct Ao3 {
+    /// The story id from the URL.
+    /// https://archiveofourown.org/works/1234 has a story id of 1234
+    #[arg(allow_hyphen_values = true)]
+    pub story_id: String,
+}
+
+impl Scraper for Ao3 {
+    fn name(&self) -> &'static str {
+        "ao3"
+    }
 
-    let navigate = format!("{HOST}/works/{series}/navigate");
+    fn feed(&self) -> String {
+        self.story_id.clone()
+    }
+
+    fn fetch(&self, etag: Option<&str>) -> Result<Fetched> {
+        get(&self.story_id, etag)
+    }
+}
 
-    let html = client.get(&navigate).send()?.bytes()?;
+fn get(series: &str, etag: Option<&str>) -> Result<Fetched> {
+    let client = http::client("ao3")?;
+
+    let url = format!("{}/works/{series}/navigate", http::base_url("ao3", HOST));
+
+    let Some(Modified { response, etag }) = http::conditional(client.get(url), etag)? else {
+        return Ok(Fetched::NotModified);
+    };
+    let html = response.bytes()?;
 
     let _span = error_span!("document", document = %String::from_utf8_lossy(&html)).entered();
 
     let html = String::from_utf8(html.into())?;
-    let doc = Html::parse_document(&html);
 
-    // Page does not have times, but using the current time is good enough
-    let now = Utc::now().to_rfc2822();
+    Ok(Feed { channel: parse(series, &html)?, etag, ..Feed::default() }.into())
+}
 
+/// Builds the feed from the story's navigate page.
+pub fn parse(series: &str, html: &str) -> Result<Channel> {
+    let doc = Html::parse_document(html);
 
     let title = doc
         .select(&Selector::parse("h2.heading > a").unwrap())
         .next()
-        .ok_or_eyre("No title")?
+        .ok_or_parse("No title")?
         .text()
         .next()
-        .ok_or_eyre("Title had no text")?;
+        .ok_or_parse("Title had no text")?;
 
-    let chapters: Vec<_> = doc
+    let chapters = doc
         .select(&Selector::parse("ol.chapter > li > a").unwrap())
         .rev()
         .map(|c| {
-            let title = c.text().next().ok_or_eyre("Chapter has no title").unwrap();
-            let href = c.attr("href").ok_or_eyre("Missing chapter link").unwrap();
+            let title = c.text().next().ok_or_parse("Chapter has no title")?;
+            let href = c.attr("href").ok_or_parse("Missing chapter link")?;
 
-            ItemBuilder::default()
+            Ok(ItemBuilder::default()
                 .title(Some(title.to_string()))
                 .link(Some(format!("{HOST}{href}")))
                 .guid(Some(GuidBuilder::default().value(href.to_string()).build()))
-                .pub_date(Some(now.clone()))
-                .build()
+                .build())
         })
-        .collect();
+        .collect::<Result<Vec<_>>>()?;
 
-    let feed = ChannelBuilder::default()
+    Ok(ChannelBuilder::default()
         .title(title.to_string())
-        .link(navigate)
+        .link(format!("{HOST}/works/{series}/navigate"))
         .ttl(Some("360".into()))
         .items(chapters)
-        .build();
-
-    print!("{feed}");
-    Ok(())
+        .build())
 }
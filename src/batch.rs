@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use crate::command::ScraperCommand;
+use crate::config::ExpandPath;
+use crate::error::{Error, Result};
+use crate::output::{self, Format};
+use crate::scraper::{Fetched, scrape};
+
+/// A list of feeds to scrape in one run.
+///
+/// ```toml
+/// # Optional, overrides --format
+/// format = "rss"
+///
+/// [[feeds]]
+/// command = ["mangadex", "975f3334-8395-4393-84a2-50fcaccbcdc0"]
+/// output = "/srv/feeds/mangadex-975f3334.xml"
+/// # Optional, overrides the format above
+/// format = "atom"
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
+    pub feeds: Vec<Entry>,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    /// The subcommand and its arguments, the same as on the command line.
+    pub command: Vec<String>,
+    #[serde_as(as = "ExpandPath")]
+    pub output: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub succeeded: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+/// Scrapes every feed in the manifest one after another, writing each to its output file.
+///
+/// Failures are collected in the summary instead of stopping the run. Per-site delays and state,
+/// like the gelbooru tag database and QQ login, are shared by all the feeds.
+pub fn run(manifest: &Path, format: Format) -> Result<Summary> {
+    let manifest = load(manifest)?;
+    let format = manifest.format.unwrap_or(format);
+
+    let mut summary = Summary::default();
+
+    for entry in manifest.feeds {
+        match run_entry(&entry, entry.format.unwrap_or(format)) {
+            Ok(()) => summary.succeeded.push(entry.output),
+            Err(e) => summary.failed.push((entry.output, e)),
+        }
+    }
+
+    Ok(summary)
+}
+
+pub(crate) fn load(manifest: &Path) -> Result<Manifest> {
+    toml::from_str(&fs::read_to_string(manifest)?).map_err(Error::config)
+}
+
+fn run_entry(entry: &Entry, format: Format) -> Result<()> {
+    let scraper = ScraperCommand::parse_args(&entry.command)
+        .map_err(Error::config)?
+        .into_scraper();
+
+    match scrape(&*scraper, None)? {
+        // Nothing to write without an etag, but leave the old file alone if it happens anyway
+        Fetched::NotModified => Ok(()),
+        Fetched::Feed(feed) => write_atomic(&entry.output, &output::render(*feed, format)),
+    }
+}
+
+// Readers never see a partially written feed
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let result = fs::write(&tmp, contents).and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        // Don't leave a stray file next to the feed if the disk filled up or the rename failed
+        let _ = fs::remove_file(&tmp);
+    }
+    Ok(result?)
+}
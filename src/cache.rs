@@ -0,0 +1,355 @@
+use std::fs::{self, File};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::DateTime;
+use reqwest::blocking::{Request, Response};
+use reqwest::header::{
+    AUTHORIZATION, CACHE_CONTROL, COOKIE, DATE, ETAG, EXPIRES, HeaderMap, IF_MODIFIED_SINCE,
+    IF_NONE_MATCH, LAST_MODIFIED, SET_COOKIE, VARY,
+};
+use reqwest::{Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::Result;
+use crate::{config, db, recording};
+
+// Temp files older than this were left by a process that died while writing them.
+const STALE_TEMP: Duration = Duration::from_secs(60 * 60);
+
+// Query parameters holding credentials.
+const CREDENTIALS: &[&str] = &["api_key", "user_id"];
+
+static PRUNED: AtomicBool = AtomicBool::new(false);
+
+/// A cached response, stored as a file named after the hash of its url with the entry on the first
+/// line followed by the body, so both are always replaced together.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// The request headers named by Vary, and what was sent for each.
+    vary: Vec<(String, Option<String>)>,
+    /// Seconds since the epoch.
+    expires: u64,
+}
+
+// None when caching is off, or while recording or replaying so every request is really made.
+fn dir() -> Result<Option<PathBuf>> {
+    if !config::get()?.cache || recording::is_active() {
+        return Ok(None);
+    }
+
+    let dir = match dirs::cache_dir() {
+        Some(dir) => dir.join("rss-scrapers"),
+        None => db::state_dir()?.join("cache"),
+    };
+    Ok(Some(dir))
+}
+
+// The url without any credentials in its query, like gelbooru's api_key, so they're never
+// written to disk. It's only kept to catch hash collisions.
+fn stored_url(url: &Url) -> String {
+    let mut url = url.clone();
+    let pairs: Vec<_> = url
+        .query_pairs()
+        .filter(|(k, _)| !CREDENTIALS.contains(&k.as_ref()))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+// The url along with the scraper's configured user agent and headers, which aren't part of the
+// request yet, so changing them refetches everything instead of reusing the old responses.
+fn key(url: &Url, scraper: &str) -> Result<String> {
+    let network = config::network(scraper, None)?;
+    let mut headers: Vec<_> = network.headers.into_iter().collect();
+    headers.sort_unstable();
+
+    let mut hasher = DefaultHasher::new();
+    (url.as_str(), network.user_agent, headers).hash(&mut hasher);
+
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Where the response to a request is cached.
+pub(crate) struct Slot {
+    scraper: &'static str,
+    dir: PathBuf,
+    key: String,
+    url: Url,
+    headers: HeaderMap,
+}
+
+/// The slot for a request, or None if caching is off or the request can't be cached: anything but
+/// a GET, anything sending credentials, or anything that asks not to be stored.
+pub(crate) fn slot(scraper: &'static str, request: &Request) -> Result<Option<Slot>> {
+    let headers = request.headers();
+    let no_store = headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .any(|v| v.to_str().is_ok_and(|v| v.split(',').any(|d| d.trim() == "no-store")));
+
+    // Responses to these are specific to the session, like QQ's logged in pages
+    if request.method() != Method::GET
+        || headers.contains_key(COOKIE)
+        || headers.contains_key(AUTHORIZATION)
+        || no_store
+    {
+        return Ok(None);
+    }
+    let Some(dir) = dir()? else {
+        return Ok(None);
+    };
+
+    let key = key(request.url(), scraper)?;
+    Ok(Some(Slot { scraper, dir, key, url: request.url().clone(), headers: headers.clone() }))
+}
+
+impl Slot {
+    /// A fresh cached response, or a 304 if the request is conditional and the cached response
+    /// matches it.
+    pub(crate) fn get(&self) -> Result<Option<Response>> {
+        let Some((entry, body)) = read(&self.dir.join(&self.key)) else {
+            return Ok(None);
+        };
+        if entry.url != stored_url(&self.url) || entry.expires <= now() {
+            return Ok(None);
+        }
+        if entry.vary.iter().any(|(name, value)| self.sent(name) != value.as_deref()) {
+            return Ok(None);
+        }
+
+        debug!("Using cached response for {}", entry.url);
+
+        let header = |name: &str| entry.headers.iter().find(|(k, _)| k == name).map(|(_, v)| v);
+
+        let not_modified =
+            match (self.sent(IF_NONE_MATCH.as_str()), self.sent(IF_MODIFIED_SINCE.as_str())) {
+                (Some(etag), _) => header(ETAG.as_str()).is_some_and(|e| e == etag),
+                (None, Some(date)) => header(LAST_MODIFIED.as_str()).is_some_and(|d| d == date),
+                (None, None) => false,
+            };
+
+        let (status, body) = match not_modified {
+            true => (StatusCode::NOT_MODIFIED.as_u16(), Vec::new()),
+            false => (entry.status, body),
+        };
+
+        Ok(Some(recording::rebuild(self.url.clone(), status, &entry.headers, body)?))
+    }
+
+    /// Saves the response if it can be cached. The response is read to save it, so a copy is
+    /// returned in its place.
+    pub(crate) fn put(self, response: Response) -> Result<Response> {
+        // Responses that set cookies are specific to this session too
+        if response.status() != StatusCode::OK || response.headers().contains_key(SET_COOKIE) {
+            return Ok(response);
+        }
+
+        // The request headers the response depends on, which have to match to reuse it
+        let mut vary = Vec::new();
+        for name in response.headers().get_all(VARY).iter().filter_map(|v| v.to_str().ok()) {
+            for name in name.split(',').map(|n| n.trim().to_lowercase()) {
+                if name == "*" {
+                    return Ok(response);
+                }
+                let value = self.sent(&name).map(str::to_string);
+                vary.push((name, value));
+            }
+        }
+
+        let Some(ttl) = ttl(response.headers(), config::cache_ttl(self.scraper)?) else {
+            return Ok(response);
+        };
+
+        let response_url = response.url().clone();
+        let status = response.status().as_u16();
+        let headers = recording::pairs(response.headers());
+        let body = response.bytes()?.to_vec();
+
+        let entry =
+            Entry { url: stored_url(&self.url), status, headers, vary, expires: now() + ttl };
+
+        fs::create_dir_all(&self.dir)?;
+        if !PRUNED.swap(true, Ordering::Relaxed) {
+            prune(&self.dir);
+        }
+
+        // Written to a temp file and renamed so other processes never read a partial entry
+        let path = self.dir.join(&self.key);
+        let tmp = self.dir.join(format!("{}.{}.tmp", self.key, process::id()));
+
+        let mut contents = serde_json::to_vec(&entry)?;
+        contents.push(b'\n');
+        contents.extend_from_slice(&body);
+
+        let result = fs::write(&tmp, contents).and_then(|()| fs::rename(&tmp, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result?;
+
+        recording::rebuild(response_url, status, &entry.headers, body)
+    }
+
+    fn sent(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+}
+
+// Anything unreadable is treated as a miss and overwritten.
+fn read(path: &Path) -> Option<(Entry, Vec<u8>)> {
+    let contents = fs::read(path).ok()?;
+    let (entry, body) = contents.split_at(contents.iter().position(|&b| b == b'\n')?);
+    Some((serde_json::from_slice(entry).ok()?, body[1..].to_vec()))
+}
+
+// Removes expired entries, and temp files left behind, so urls that are never requested again
+// don't stay forever. Failures are left for the next process to try.
+fn prune(dir: &Path) {
+    let Ok(files) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for file in files.filter_map(|f| f.ok()).map(|f| f.path()) {
+        let expired = if file.extension().is_some_and(|e| e == "tmp") {
+            let modified = fs::metadata(&file).and_then(|m| m.modified());
+            modified.is_ok_and(|m| m.elapsed().is_ok_and(|age| age > STALE_TEMP))
+        } else {
+            header(&file).is_none_or(|e| e.expires <= now())
+        };
+
+        if expired {
+            debug!("Removing expired cache entry {}", file.display());
+            let _ = fs::remove_file(file);
+        }
+    }
+}
+
+// Only the entry, without reading the whole body.
+fn header(path: &Path) -> Option<Entry> {
+    let mut line = Vec::new();
+    BufReader::new(File::open(path).ok()?).read_until(b'\n', &mut line).ok()?;
+    serde_json::from_slice(line.strip_suffix(b"\n")?).ok()
+}
+
+// How many seconds the response can be reused for, from Cache-Control or Expires, raised to the
+// scraper's minimum even when it says not to cache it. None if it can't be cached.
+fn ttl(headers: &HeaderMap, min_ttl: Option<Duration>) -> Option<u64> {
+    let cache_control = headers.get(CACHE_CONTROL).and_then(|v| v.to_str().ok()).unwrap_or("");
+    let directives: Vec<_> = cache_control.split(',').map(str::trim).collect();
+
+    let date = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|d| DateTime::parse_from_rfc2822(d).ok())
+    };
+
+    let ttl = if directives.iter().any(|d| *d == "no-cache" || *d == "no-store") {
+        Some(0)
+    } else if let Some(age) = directives.iter().find_map(|d| d.strip_prefix("max-age=")) {
+        age.parse().ok()
+    } else {
+        // Expires is relative to the server's clock
+        let expires = date(EXPIRES);
+        let now = date(DATE).map_or_else(|| now() as i64, |d| d.timestamp());
+        expires.map(|e| (e.timestamp() - now).max(0) as u64)
+    };
+
+    let ttl = ttl.unwrap_or(0).max(min_ttl.map_or(0, |t| t.as_secs()));
+    (ttl > 0).then_some(ttl)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    const MIN: Option<Duration> = Some(Duration::from_secs(600));
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.parse().unwrap(), HeaderValue::from_str(v).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn max_age() {
+        assert_eq!(ttl(&headers(&[("cache-control", "public, max-age=60")]), None), Some(60));
+        assert_eq!(ttl(&headers(&[("cache-control", "max-age=0")]), None), None);
+    }
+
+    #[test]
+    fn max_age_over_expires() {
+        let headers = headers(&[
+            ("cache-control", "max-age=60"),
+            ("date", "Sun, 18 Oct 2026 06:00:00 GMT"),
+            ("expires", "Sun, 18 Oct 2026 07:00:00 GMT"),
+        ]);
+        assert_eq!(ttl(&headers, None), Some(60));
+    }
+
+    #[test]
+    fn expires_from_date() {
+        let future = headers(&[
+            ("date", "Sun, 18 Oct 2026 06:00:00 GMT"),
+            ("expires", "Sun, 18 Oct 2026 06:05:00 GMT"),
+        ]);
+        assert_eq!(ttl(&future, None), Some(300));
+
+        let past = headers(&[
+            ("date", "Sun, 18 Oct 2026 06:00:00 GMT"),
+            ("expires", "Sun, 18 Oct 2026 05:00:00 GMT"),
+        ]);
+        assert_eq!(ttl(&past, None), None);
+    }
+
+    #[test]
+    fn not_cacheable() {
+        for cache_control in ["no-cache", "no-store", "no-store, max-age=60"] {
+            let headers = headers(&[("cache-control", cache_control)]);
+            assert_eq!(ttl(&headers, None), None, "{cache_control}");
+            assert_eq!(ttl(&headers, MIN), Some(600), "{cache_control}");
+        }
+        assert_eq!(ttl(&HeaderMap::new(), None), None);
+    }
+
+    #[test]
+    fn min_ttl() {
+        assert_eq!(ttl(&headers(&[("cache-control", "max-age=60")]), MIN), Some(600));
+        assert_eq!(ttl(&headers(&[("cache-control", "max-age=6000")]), MIN), Some(6000));
+        assert_eq!(ttl(&HeaderMap::new(), MIN), Some(600));
+    }
+
+    #[test]
+    fn credentials_not_stored() {
+        let url = |url| Url::parse(url).unwrap();
+
+        assert_eq!(
+            stored_url(&url("https://gelbooru.com/index.php?page=dapi&user_id=1&api_key=abc")),
+            "https://gelbooru.com/index.php?page=dapi"
+        );
+        assert_eq!(stored_url(&url("https://gelbooru.com/?api_key=abc")), "https://gelbooru.com/");
+        assert_eq!(stored_url(&url("https://nyaa.si/?page=rss")), "https://nyaa.si/?page=rss");
+    }
+}
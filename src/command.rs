@@ -0,0 +1,58 @@
+use clap::{Parser, Subcommand};
+
+use crate::scraper::Scraper;
+use crate::{ao3, gelbooru, jnovel, mangadex, qq, royalroad, seasonal_anime, tfgames, vn_news};
+
+/// The scraper subcommands, shared by the command line and anything else that names feeds the
+/// same way, like batch manifests.
+#[derive(Debug, Clone, Subcommand)]
+pub enum ScraperCommand {
+    /// Archive Of Our Own
+    Ao3(ao3::Ao3),
+    /// Gelbooru Rss
+    /// Uses $HOME/.rss/geltagblacklist
+    Gelbooru(gelbooru::Gelbooru),
+    /// Jnovel-club series
+    Jnovel(jnovel::Jnovel),
+    /// Mangadex series
+    Mangadex(mangadex::Mangadex),
+    // QQ
+    QQ(qq::QQ),
+    RoyalRoad,
+    SeasonalAnime,
+    Tfgames(tfgames::Tfgames),
+    VnNews,
+}
+
+#[derive(Debug, Parser)]
+#[command(no_binary_name = true)]
+struct Args {
+    #[command(subcommand)]
+    cmd: ScraperCommand,
+}
+
+impl ScraperCommand {
+    /// Parses a command from its arguments, without the binary name, like
+    /// `["mangadex", "975f3334-8395-4393-84a2-50fcaccbcdc0"]`.
+    pub fn parse_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        Args::try_parse_from(args).map(|a| a.cmd)
+    }
+
+    pub fn into_scraper(self) -> Box<dyn Scraper> {
+        match self {
+            Self::Ao3(s) => Box::new(s),
+            Self::Gelbooru(s) => Box::new(s),
+            Self::Jnovel(s) => Box::new(s),
+            Self::Mangadex(s) => Box::new(s),
+            Self::QQ(s) => Box::new(s),
+            Self::RoyalRoad => Box::new(royalroad::RoyalRoad),
+            Self::SeasonalAnime => Box::new(seasonal_anime::SeasonalAnime),
+            Self::Tfgames(s) => Box::new(s),
+            Self::VnNews => Box::new(vn_news::VnNews),
+        }
+    }
+}
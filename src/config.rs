@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{DeserializeAs, DurationSecondsWithFrac, SerializeAs, serde_as};
+
+use crate::error::{Error, Result};
+use crate::filter::Rule;
+use crate::output::Format;
+use crate::throttle::Limit;
+
+/// Settings from the optional `rss-scrapers.toml`, shared by every scraper, with a section for
+/// each scraper that has its own settings. See `rss-scrapers.toml.sample`.
+#[serde_as]
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    pub user_agent: Option<String>,
+    /// An http, https or socks5 proxy for every request.
+    pub proxy: Option<String>,
+    /// How long a request can take before it fails, 30 seconds by default.
+    #[serde(default)]
+    #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
+    pub timeout: Option<Duration>,
+    /// Sent with every request.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Overrides every scraper's default delay between requests to the same site.
+    #[serde(default)]
+    #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
+    pub delay: Option<Duration>,
+    /// How many requests can be sent to a site at once before the delay applies, 1 by default.
+    pub burst: Option<u32>,
+    /// Where databases are kept, see [`state_dir`] for the default.
+    #[serde(default)]
+    #[serde_as(as = "Option<ExpandPath>")]
+    pub state_dir: Option<PathBuf>,
+    /// The default for --format.
+    pub format: Option<Format>,
+    /// Whether to keep responses on disk for as long as the site's Cache-Control or Expires
+    /// allows, or a scraper's `cache_ttl`.
+    #[serde(default)]
+    pub cache: bool,
+    #[serde(default)]
+    pub(crate) filters: Vec<Rule>,
+    /// Settings for each scraper's requests, like `[sites.mangadex]`.
+    #[serde(default)]
+    pub(crate) sites: HashMap<String, Site>,
+    /// Tables named after the scrapers, like `[gelbooru]`.
+    #[serde(flatten)]
+    pub(crate) scrapers: HashMap<String, toml::Table>,
+}
+
+/// Deserializes a path, expanding `~`, `$VAR` and `${VAR}` so configs can be shared between
+/// machines. Unset variables are an error rather than being left in the path.
+pub(crate) struct ExpandPath;
+
+impl<'de> DeserializeAs<'de, PathBuf> for ExpandPath {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        let path = String::deserialize(deserializer)?;
+        shellexpand::full(&path)
+            .map(|p| PathBuf::from(p.as_ref()))
+            .map_err(|e| D::Error::custom(format!("can't expand {path:?}: {e}")))
+    }
+}
+
+// Paths are written back out as they are, for generated configs
+impl SerializeAs<PathBuf> for ExpandPath {
+    fn serialize_as<S: Serializer>(path: &PathBuf, serializer: S) -> Result<S::Ok, S::Error> {
+        path.serialize(serializer)
+    }
+}
+
+pub fn get() -> Result<&'static Config> {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+
+    let config = awconf::load_config("rss-scrapers", None::<&str>, Some(""))
+        .map_err(Error::config)?
+        .0;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The scraper's section of `rss-scrapers.toml`, or the scraper's own config file, like
+/// `gelbooru-rss.toml`, from before there was a shared one. `default` is used when neither
+/// exists, and None means the config is required.
+pub(crate) fn section<T: DeserializeOwned>(
+    scraper: &str,
+    legacy: &str,
+    default: Option<&str>,
+) -> Result<T> {
+    if let Some(section) = table(scraper)? {
+        return Ok(section);
+    }
+
+    Ok(awconf::load_config(legacy, None::<&str>, default).map_err(Error::config)?.0)
+}
+
+/// Whether the scraper has a section in `rss-scrapers.toml` or its own config file.
+pub(crate) fn is_configured(scraper: &str, legacy: &str) -> Result<bool> {
+    if get()?.scrapers.contains_key(scraper) {
+        return Ok(true);
+    }
+
+    let (_, path): (toml::Table, _) =
+        awconf::load_config(legacy, None::<&str>, Some("")).map_err(Error::config)?;
+    Ok(path.is_some())
+}
+
+/// Only the scraper's section of `rss-scrapers.toml`, if it has one.
+pub(crate) fn table<T: DeserializeOwned>(scraper: &str) -> Result<Option<T>> {
+    get()?
+        .scrapers
+        .get(scraper)
+        .map(|table| table.clone().try_into())
+        .transpose()
+        .map_err(|e| Error::config(format!("[{scraper}]: {e}")))
+}
+
+/// Overrides for the requests a scraper makes, kept out of the scraper's own section so setting
+/// them never hides its legacy config file.
+#[serde_as]
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Site {
+    #[serde(default)]
+    #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
+    delay: Option<Duration>,
+    burst: Option<u32>,
+    #[serde(default)]
+    #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
+    cache_ttl: Option<Duration>,
+    user_agent: Option<String>,
+    proxy: Option<String>,
+    #[serde(default)]
+    #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
+    timeout: Option<Duration>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+}
+
+/// How a scraper's requests are sent.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Network {
+    pub user_agent: Option<String>,
+    pub proxy: Option<String>,
+    pub timeout: Option<Duration>,
+    pub headers: HashMap<String, String>,
+}
+
+/// The rate limit for a scraper's requests, with each setting from the scraper's `[sites]` table,
+/// the global setting, or the scraper's own default, in that order. None if there's no delay.
+pub(crate) fn rate_limit(scraper: &str, default_delay: Option<Duration>) -> Result<Option<Limit>> {
+    let config = get()?;
+    let site = config.sites.get(scraper);
+
+    let Some(delay) = site.and_then(|s| s.delay).or(config.delay).or(default_delay) else {
+        return Ok(None);
+    };
+
+    Ok(Some(Limit { delay, burst: site.and_then(|s| s.burst).or(config.burst).unwrap_or(1) }))
+}
+
+/// The network settings for a scraper, with each from the scraper's `[sites]` table or the global
+/// one. Headers from both are sent, with the site's replacing global ones of the same name.
+///
+/// `user_agent` is used instead of the global user agent for sites that need their own.
+pub(crate) fn network(scraper: &str, user_agent: Option<&str>) -> Result<Network> {
+    let config = get()?;
+    let site = config.sites.get(scraper);
+
+    // Header names aren't case sensitive
+    let mut headers: HashMap<_, _> =
+        config.headers.iter().map(|(k, v)| (k.to_lowercase(), v.clone())).collect();
+    if let Some(site) = site {
+        headers.extend(site.headers.iter().map(|(k, v)| (k.to_lowercase(), v.clone())));
+    }
+
+    Ok(Network {
+        user_agent: site
+            .and_then(|s| s.user_agent.clone())
+            .or(user_agent.map(str::to_string))
+            .or_else(|| config.user_agent.clone()),
+        proxy: site.and_then(|s| s.proxy.clone()).or_else(|| config.proxy.clone()),
+        timeout: site.and_then(|s| s.timeout).or(config.timeout),
+        headers,
+    })
+}
+
+/// How long the scraper's responses are cached for at least, even if the site says they can't be.
+pub(crate) fn cache_ttl(scraper: &str) -> Result<Option<Duration>> {
+    Ok(get()?.sites.get(scraper).and_then(|s| s.cache_ttl))
+}
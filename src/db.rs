@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fs, thread};
+
+use rocksdb::DB;
+
+use crate::config;
+use crate::error::{Error, Result};
+
+// Only one process can hold a database open at a time, and aw-rss may be running several scrapers.
+const LOCK_RETRIES: usize = 100;
+const LOCK_DELAY: Duration = Duration::from_millis(100);
+
+/// A path under the state directory, where all persistent state is kept by default.
+pub(crate) fn default_path(name: &str) -> Result<PathBuf> {
+    Ok(state_dir()?.join(name))
+}
+
+/// The configured `state_dir`, otherwise `$HOME/.rss` if it already exists so older setups keep
+/// their databases, otherwise `$XDG_STATE_HOME/rss-scrapers`.
+pub(crate) fn state_dir() -> Result<PathBuf> {
+    if let Some(dir) = &config::get()?.state_dir {
+        return Ok(dir.clone());
+    }
+
+    let legacy = dirs::home_dir().map(|home| home.join(".rss"));
+    if let Some(legacy) = legacy.filter(|l| l.is_dir()) {
+        return Ok(legacy);
+    }
+
+    // There's no state directory outside of Linux
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("rss-scrapers"))
+        .ok_or_else(|| Error::config("no home directory, set state_dir"))
+}
+
+pub(crate) fn open(path: &Path) -> Result<DB> {
+    // rocksdb only creates the last directory
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut opts = rocksdb::Options::default();
+    opts.create_if_missing(true);
+    opts.create_missing_column_families(true);
+    opts.set_compression_type(rocksdb::DBCompressionType::Lz4);
+    opts.set_max_open_files(100);
+    opts.set_keep_log_file_num(10);
+
+    let mut attempts = 0;
+    loop {
+        match DB::open(&opts, path) {
+            Ok(db) => return Ok(db),
+            // Another process still holds it, which repairing would only make worse
+            Err(e) if is_locked(&e) => {
+                attempts += 1;
+                if attempts >= LOCK_RETRIES {
+                    return Err(e.into());
+                }
+                thread::sleep(LOCK_DELAY);
+            }
+            // Anything else is most likely corruption from a process that was killed
+            Err(_) => break,
+        }
+    }
+
+    DB::repair(&opts, path)?;
+    Ok(DB::open(&opts, path)?)
+}
+
+// Like "IO error: While lock file: <path>/LOCK: Resource temporarily unavailable". Only checking
+// for "lock" would match corruption like "block checksum mismatch" too.
+fn is_locked(e: &rocksdb::Error) -> bool {
+    e.kind() == rocksdb::ErrorKind::IOError && e.to_string().contains("While lock file")
+}
@@ -0,0 +1,123 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process;
+
+use crate::error::{Error, Result};
+use crate::{config, gelbooru, mangadex, qq, seasonal_anime};
+
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+}
+
+#[derive(Debug)]
+pub enum Status {
+    Pass(String),
+    /// The check doesn't apply, usually because the scraper isn't configured.
+    Skip(String),
+    Fail(Error),
+}
+
+/// Checks every config file, the QQ login and cookie jar, and the databases, so problems show
+/// up before a feed is added rather than when aw-rss first runs it.
+///
+/// Checks run in order and later checks are skipped when the ones they depend on fail. QQ logs
+/// in against `RSS_SCRAPERS_QQ_URL` when it's set.
+pub fn run() -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut check = |name, status| {
+        checks.push(Check { name, status });
+    };
+
+    if let Err(e) = config::get() {
+        check("rss-scrapers.toml", Status::Fail(e));
+        return checks;
+    }
+    check("rss-scrapers.toml", Status::Pass("parsed".to_string()));
+
+    match configured("gelbooru", "gelbooru-rss", || gelbooru::config().map(drop)) {
+        status @ Status::Fail(_) => check("gelbooru config", status),
+        status => {
+            check("gelbooru config", status);
+            check(
+                "gelbooru tag database",
+                result(gelbooru::tag_db().and_then(|_| gelbooru::tag_db_path()), |path| {
+                    format!("opened {}", path.display())
+                }),
+            );
+        }
+    }
+
+    check(
+        "seasonal-anime config",
+        configured("seasonal-anime", "seasonal-anime-rss", || seasonal_anime::config().map(drop)),
+    );
+
+    check(
+        "mangadex blocked groups",
+        result(mangadex::blocked_groups(), |groups| format!("{} blocked groups", groups.len())),
+    );
+
+    match configured("qq", "qq-rss", || qq::cookie_jar().map(drop)) {
+        status @ Status::Pass(_) => {
+            check("qq config", status);
+
+            let jar = qq::cookie_jar().and_then(|jar| cookie_jar(&jar).map(|()| jar));
+            match jar {
+                Ok(jar) => {
+                    check("qq cookie jar", Status::Pass(format!("{} is writable", jar.display())));
+                    check("qq login", result(qq::check_login(), |()| "logged in".to_string()));
+                }
+                Err(e) => {
+                    check("qq cookie jar", Status::Fail(e));
+                    check("qq login", Status::Skip("no cookie jar".to_string()));
+                }
+            }
+        }
+        status => check("qq config", status),
+    }
+
+    checks
+}
+
+fn result<T>(result: Result<T>, pass: impl FnOnce(T) -> String) -> Status {
+    match result {
+        Ok(t) => Status::Pass(pass(t)),
+        Err(e) => Status::Fail(e),
+    }
+}
+
+// Skips scrapers with neither a section in rss-scrapers.toml nor their own config file.
+fn configured(scraper: &str, legacy: &str, load: impl FnOnce() -> Result<()>) -> Status {
+    match config::is_configured(scraper, legacy) {
+        Ok(true) => result(load(), |()| "parsed".to_string()),
+        Ok(false) => Status::Skip("not configured".to_string()),
+        Err(e) => Status::Fail(e),
+    }
+}
+
+// The jar has to be readable to keep the login and writable to save it. Missing jars are created
+// on the first login, so only the directory has to exist then.
+fn cookie_jar(jar: &Path) -> Result<()> {
+    match File::open(jar) {
+        Ok(_) => {
+            OpenOptions::new().append(true).open(jar)?;
+            Ok(())
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let dir = jar.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            // The permission bits don't say whether this user can write there
+            let probe = dir.join(format!(".rss-scrapers-doctor-{}", process::id()));
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&probe)
+                .map_err(|e| Error::config(format!("{} isn't writable: {e}", dir.display())))?;
+            fs::remove_file(probe)?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
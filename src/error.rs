@@ -0,0 +1,163 @@
+use std::fmt;
+use std::string::FromUtf8Error;
+
+use tracing_error::TracedError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Captures the current span trace when created so the response bodies recorded in
+/// `error_span!`s are still printed after the error has left the scraper.
+#[derive(Debug)]
+pub struct Error {
+    category: Category,
+    source: TracedError<ErrorKind>,
+}
+
+/// Broad kinds of failure, so callers can decide whether to retry a feed or alert someone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// The site couldn't be reached or kept failing
+    Network,
+    /// The site rejected the login
+    Auth,
+    /// The site returned something the scraper didn't understand
+    Parse,
+    /// The site is rate limiting requests
+    RateLimit,
+    Config,
+    Other,
+}
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Network => "network",
+            Self::Auth => "auth",
+            Self::Parse => "parse",
+            Self::RateLimit => "rate-limit",
+            Self::Config => "config",
+            Self::Other => "other",
+        }
+    }
+
+    /// Follows sysexits.h where there's a matching code.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Network => 69,
+            Self::Auth => 77,
+            Self::Parse => 65,
+            Self::RateLimit => 75,
+            Self::Config => 78,
+            Self::Other => 1,
+        }
+    }
+
+    /// Whether trying again later could succeed without anyone changing anything.
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::Network | Self::RateLimit)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ErrorKind {
+    #[error("request failed")]
+    Http(#[from] reqwest::Error),
+    /// For statuses that were still failing after retrying, or that the scraper can't use.
+    #[error("server responded with {0}")]
+    Status(reqwest::StatusCode),
+    #[error("invalid json")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid rss")]
+    Rss(#[from] rss::Error),
+    #[error("response was not valid utf-8")]
+    Utf8(#[from] FromUtf8Error),
+    #[error("invalid url")]
+    Url(#[from] url::ParseError),
+    #[error("invalid date")]
+    Date(#[from] chrono::ParseError),
+    #[error("database error")]
+    Db(#[from] rocksdb::Error),
+    #[error("aw-rss database error")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("io error")]
+    Io(#[from] std::io::Error),
+    #[error("invalid config: {0}")]
+    Config(String),
+    #[error("authentication failed: {0}")]
+    Auth(String),
+    /// The site returned something the scraper didn't expect, usually because the page changed.
+    #[error("{0}")]
+    Parse(String),
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.source()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.source, f)
+    }
+}
+
+impl<E> From<E> for Error
+where
+    ErrorKind: From<E>,
+{
+    fn from(source: E) -> Self {
+        let kind = ErrorKind::from(source);
+        Self { category: kind.category(), source: kind.into() }
+    }
+}
+
+impl ErrorKind {
+    fn category(&self) -> Category {
+        match self {
+            Self::Http(_) => Category::Network,
+            Self::Status(s) if *s == reqwest::StatusCode::TOO_MANY_REQUESTS => Category::RateLimit,
+            Self::Status(reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => {
+                Category::Auth
+            }
+            Self::Status(_) => Category::Network,
+            Self::Json(_)
+            | Self::Rss(_)
+            | Self::Utf8(_)
+            | Self::Url(_)
+            | Self::Date(_)
+            | Self::Parse(_) => Category::Parse,
+            Self::Db(_) | Self::Sqlite(_) | Self::Io(_) => Category::Other,
+            Self::Config(_) => Category::Config,
+            Self::Auth(_) => Category::Auth,
+        }
+    }
+}
+
+impl Error {
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    pub(crate) fn parse(msg: impl Into<String>) -> Self {
+        ErrorKind::Parse(msg.into()).into()
+    }
+
+    pub(crate) fn config(e: impl fmt::Display) -> Self {
+        ErrorKind::Config(e.to_string()).into()
+    }
+
+    pub(crate) fn auth(msg: impl Into<String>) -> Self {
+        ErrorKind::Auth(msg.into()).into()
+    }
+}
+
+pub(crate) trait OptionExt<T> {
+    fn ok_or_parse(self, msg: &'static str) -> Result<T>;
+}
+
+impl<T> OptionExt<T> for Option<T> {
+    fn ok_or_parse(self, msg: &'static str) -> Result<T> {
+        self.ok_or_else(|| Error::parse(msg))
+    }
+}
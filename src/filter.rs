@@ -0,0 +1,233 @@
+use std::fmt;
+
+use regex::Regex;
+use rss::Item;
+use serde::Deserialize;
+use serde_with::{DisplayFromStr, serde_as};
+use tracing::debug;
+
+use crate::Scraper;
+use crate::scraper::Feed;
+
+/// Drops items from a scraper's feeds based on a regex over one of their fields.
+///
+/// ```toml
+/// [[filters]]
+/// scraper = "vn-news"
+/// # Optional, only applies to this feed instead of every feed from the scraper
+/// feed = ""
+/// # title, link or category
+/// field = "title"
+/// # Items are dropped if they match exclude, or if they don't match include
+/// exclude = "(?i)h-rpg"
+/// ```
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub(crate) struct Rule {
+    scraper: String,
+    feed: Option<String>,
+    field: Field,
+    #[serde(default)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    include: Option<Regex>,
+    #[serde(default)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    exclude: Option<Regex>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Field {
+    Title,
+    Link,
+    Category,
+}
+
+impl Rule {
+    fn applies_to(&self, scraper: &dyn Scraper) -> bool {
+        self.scraper == scraper.name() && self.feed.as_ref().is_none_or(|f| *f == scraper.feed())
+    }
+
+    fn keeps(&self, item: &Item) -> bool {
+        let values: Vec<&str> = match self.field {
+            Field::Title => item.title().into_iter().collect(),
+            Field::Link => item.link().into_iter().collect(),
+            Field::Category => item.categories().iter().map(rss::Category::name).collect(),
+        };
+
+        let included = self.include.as_ref().is_none_or(|re| values.iter().any(|v| re.is_match(v)));
+        let excluded =
+            self.exclude.as_ref().is_some_and(|re| values.iter().any(|v| re.is_match(v)));
+
+        included && !excluded
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.field {
+            Field::Title => "title",
+            Field::Link => "link",
+            Field::Category => "category",
+        })?;
+        if let Some(re) = &self.include {
+            write!(f, " include /{re}/")?;
+        }
+        if let Some(re) = &self.exclude {
+            write!(f, " exclude /{re}/")?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes every item that one of the rules for this scraper's feed rejects.
+pub(crate) fn apply(rules: &[Rule], scraper: &dyn Scraper, feed: &mut Feed) {
+    let rules: Vec<_> = rules.iter().filter(|r| r.applies_to(scraper)).collect();
+    if rules.is_empty() {
+        return;
+    }
+
+    let mut items = feed.channel.items().to_vec();
+    items.retain(|item| {
+        let Some(rule) = rules.iter().find(|r| !r.keeps(item)) else {
+            return true;
+        };
+
+        debug!(%rule, title = item.title(), link = item.link(), "Filtered out item");
+        if let Some(guid) = item.guid() {
+            feed.extras.remove(guid.value());
+        }
+        false
+    });
+
+    feed.channel.set_items(items);
+}
+
+#[cfg(test)]
+mod tests {
+    use rss::{CategoryBuilder, Channel, GuidBuilder, ItemBuilder};
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::Result;
+    use crate::scraper::Fetched;
+
+    struct Mangadex(&'static str);
+
+    impl Scraper for Mangadex {
+        fn name(&self) -> &'static str {
+            "mangadex"
+        }
+
+        fn feed(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn fetch(&self, _etag: Option<&str>) -> Result<Fetched> {
+            unreachable!()
+        }
+    }
+
+    fn rules(toml: &str) -> Vec<Rule> {
+        #[derive(Deserialize)]
+        struct Config {
+            filters: Vec<Rule>,
+        }
+
+        toml::from_str::<Config>(toml).unwrap().filters
+    }
+
+    fn feed() -> Feed {
+        let item = |title: &str, link: &str, category: &str| {
+            ItemBuilder::default()
+                .title(Some(title.to_string()))
+                .link(Some(link.to_string()))
+                .guid(Some(GuidBuilder::default().value(link.to_string()).build()))
+                .categories(vec![CategoryBuilder::default().name(category).build()])
+                .build()
+        };
+
+        let mut channel = Channel::default();
+        channel.set_items(vec![
+            item("Title - Volume 2, Chapter 12 - The End", "https://example.com/12", "official"),
+            item("Title - Chapter 11.5 (External)", "https://example.com/11.5", "extra"),
+            item("Title - Oneshot", "https://other.example.com/1", "official"),
+        ]);
+
+        let mut feed = Feed { channel, ..Feed::default() };
+        for item in feed.channel.items() {
+            feed.extras.insert(item.link().unwrap().to_string(), Default::default());
+        }
+        feed
+    }
+
+    fn titles(feed: &Feed) -> Vec<&str> {
+        feed.channel.items().iter().filter_map(Item::title).collect()
+    }
+
+    #[test]
+    fn include() {
+        let rules = rules(
+            r#"
+            [[filters]]
+            scraper = "mangadex"
+            field = "title"
+            include = "Chapter \\d+( |$)"
+            "#,
+        );
+
+        let mut feed = feed();
+        apply(&rules, &Mangadex("a"), &mut feed);
+
+        assert_eq!(titles(&feed), ["Title - Volume 2, Chapter 12 - The End"]);
+        assert_eq!(feed.extras.len(), 1);
+    }
+
+    #[test]
+    fn exclude_link_and_category() {
+        let rules = rules(
+            r#"
+            [[filters]]
+            scraper = "mangadex"
+            field = "link"
+            exclude = "^https://other\\."
+
+            [[filters]]
+            scraper = "mangadex"
+            field = "category"
+            exclude = "^extra$"
+            "#,
+        );
+
+        let mut feed = feed();
+        apply(&rules, &Mangadex("a"), &mut feed);
+
+        assert_eq!(titles(&feed), ["Title - Volume 2, Chapter 12 - The End"]);
+    }
+
+    #[test]
+    fn scoped_to_scraper_and_feed() {
+        let rules = rules(
+            r#"
+            [[filters]]
+            scraper = "vn-news"
+            field = "title"
+            exclude = ""
+
+            [[filters]]
+            scraper = "mangadex"
+            feed = "b"
+            field = "title"
+            exclude = ""
+            "#,
+        );
+
+        let mut feed = feed();
+        apply(&rules, &Mangadex("a"), &mut feed);
+        assert_eq!(feed.channel.items().len(), 3);
+
+        apply(&rules, &Mangadex("b"), &mut feed);
+        assert!(feed.channel.items().is_empty());
+        assert!(feed.extras.is_empty());
+    }
+}
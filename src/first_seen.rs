@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use rss::Channel;
+
+use crate::Scraper;
+use crate::error::{Error, Result};
+
+/// Gives items from sites that don't publish dates the time they were first scraped, so they
+/// keep the same date on every run.
+pub(crate) fn stamp(scraper: &dyn Scraper, channel: &mut Channel) -> Result<()> {
+    if channel.items().iter().all(|i| i.pub_date().is_some()) {
+        return Ok(());
+    }
+
+    let db = crate::db::open(&crate::db::default_path("firstseen")?)?;
+
+    let now = Utc::now().timestamp();
+    let prefix = format!("{}\0{}\0", scraper.name(), scraper.feed());
+
+    for item in channel.items_mut().iter_mut().filter(|i| i.pub_date().is_none()) {
+        let Some(guid) = item.guid().map(|g| g.value().to_string()) else {
+            continue;
+        };
+        let key = prefix.clone() + &guid;
+
+        let seen = match db.get(&key)? {
+            Some(v) => i64::from_be_bytes(
+                v.as_slice()
+                    .try_into()
+                    .map_err(|_| Error::parse(format!("Invalid first seen time for {key:?}")))?,
+            ),
+            None => {
+                db.put(&key, now.to_be_bytes())?;
+                now
+            }
+        };
+
+        let seen = DateTime::from_timestamp(seen, 0).unwrap_or_default();
+        item.set_pub_date(seen.to_rfc2822());
+    }
+
+    db.flush()?;
+    Ok(())
+}
@@ -1,27 +1,30 @@
-use std::collections::HashSet;
-use std::sync::LazyLock;
-use std::thread;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::OnceLock;
 use std::time::Duration;
 
 use chrono::DateTime;
-use color_eyre::Result;
-use color_eyre::eyre::{bail, eyre};
+use clap::Args;
 use reqwest::Url;
-use reqwest::blocking::Client;
 use rocksdb::DB;
 use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
 use serde::Deserialize;
 use serde_with::{NoneAsEmptyString, serde_as};
 use tracing::error_span;
 
-static CONFIG: LazyLock<Config> =
-    LazyLock::new(|| awconf::load_config("gelbooru-rss", None::<&str>, Some("")).unwrap().0);
+use crate::config::ExpandPath;
+use crate::error::{Error, Result};
+use crate::http::{self, Client, Modified};
+use crate::scraper::{Extras, Feed, Fetched, Scraper};
+use crate::{config, db};
 
 const DELAY: Duration = Duration::from_secs(1);
 
+const HOST: &str = "https://gelbooru.com";
+
 #[serde_as]
 #[derive(Debug, Deserialize)]
-struct Config {
+pub(crate) struct Config {
     #[serde(default)]
     #[serde_as(as = "NoneAsEmptyString")]
     user_id: Option<String>,
@@ -29,60 +32,129 @@ struct Config {
     #[serde_as(as = "NoneAsEmptyString")]
     api_key: Option<String>,
     #[serde(default)]
-    #[serde_as(as = "NoneAsEmptyString")]
-    tag_db: Option<String>,
+    #[serde_as(as = "Option<ExpandPath>")]
+    tag_db: Option<PathBuf>,
     #[serde(default)]
     blacklist: HashSet<String>,
 }
 
-pub fn get(query: Vec<String>) -> Result<()> {
-    let config = LazyLock::force(&CONFIG);
-    let db = open_db()?;
-    let client = Client::new();
+#[derive(Debug, Clone, Args)]
+pub struct Gelbooru {
+    #[arg(allow_hyphen_values = true, required=true, num_args=1..)]
+    pub query: Vec<String>,
+}
 
-    let mut tags = query.iter().map(|q| urlencoding::encode(q)).collect::<Vec<_>>().join("+");
+impl Scraper for Gelbooru {
+    fn name(&self) -> &'static str {
+        "gelbooru"
+    }
+
+    fn feed(&self) -> String {
+        self.query.join(" ")
+    }
+
+    fn fetch(&self, etag: Option<&str>) -> Result<Fetched> {
+        get(&self.query, etag)
+    }
+}
+
+pub(crate) fn config() -> Result<&'static Config> {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+
+    let config = config::section("gelbooru", "gelbooru-rss", Some(""))?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+fn get(query: &[String], etag: Option<&str>) -> Result<Fetched> {
+    let config = config()?;
+    let db = tag_db()?;
+    let client = http::builder("gelbooru", None)?.delay(DELAY).build()?;
+
+    let tags = query.iter().map(|q| urlencoding::encode(q)).collect::<Vec<_>>().join("+");
 
     let mut api_url = Url::parse(&format!(
-        "https://gelbooru.com/index.php?page=dapi&s=post&q=index&json=1&tags={tags}"
+        "{}/index.php?page=dapi&s=post&q=index&json=1&tags={tags}",
+        http::base_url("gelbooru", HOST)
     ))?;
 
-    add_api_params(&mut api_url);
+    add_api_params(&mut api_url)?;
+
+    let Some(Modified { response, etag }) = http::conditional(client.get(api_url), etag)? else {
+        return Ok(Fetched::NotModified);
+    };
+    let response = response.bytes()?;
 
-    let response = client.get(api_url).send()?.bytes()?;
+    let feed = parse(query, &config.blacklist, &response, |tags| tag_types(&client, &db, tags))?;
 
-    let _span = error_span!("response", response = %String::from_utf8_lossy(&response)).entered();
+    db.flush()?;
+
+    Ok(Feed { etag, ..feed }.into())
+}
 
-    let index: IndexResponse = serde_json::from_slice(&response)?;
+/// Builds the feed from a post index response. `tag_types` returns the type of every tag on the
+/// posts that weren't blacklisted, which decides which tags are used in titles.
+pub fn parse(
+    query: &[String],
+    blacklist: &HashSet<String>,
+    index: &[u8],
+    tag_types: impl FnOnce(&[&str]) -> Result<HashMap<String, u8>>,
+) -> Result<Feed> {
+    let span = error_span!("response", response = %String::from_utf8_lossy(index)).entered();
+
+    let index: IndexResponse = serde_json::from_slice(index)?;
     let mut matched_blacklist_tags = HashSet::new();
+    let mut extras = HashMap::new();
 
-    let items = index
+    let posts: Vec<_> = index
         .post
         .into_iter()
         .filter(|p| {
-            if let Some(b) = p.tags.split(' ').find(|t| config.blacklist.contains(*t)) {
-                matched_blacklist_tags.insert(config.blacklist.get(b).unwrap().as_str());
+            if let Some(b) = p.tags.split(' ').find(|t| blacklist.contains(*t)) {
+                matched_blacklist_tags.insert(blacklist.get(b).unwrap().as_str());
                 return false;
             }
             true
         })
+        .collect();
+
+    let mut tags: Vec<_> = posts.iter().flat_map(|p| p.tags.split(' ')).collect();
+    tags.sort_unstable();
+    tags.dedup();
+
+    // Keep the whole response out of the logs for the tag requests
+    let span = span.exit();
+    let tag_types = tag_types(&tags)?;
+    let _span = span.entered();
+
+    let items = posts
+        .into_iter()
         .map(|p| {
-            let title = get_title_for_image(&client, &db, &p, &query)?;
+            let title = get_title_for_image(&tag_types, &p, query)?;
 
             // Mon Dec 05 08:26:31 -0600 2022
             let pub_date = DateTime::parse_from_str(&p.created_at, "%a %b %d %H:%M:%S %z %Y")?
                 .to_utc()
                 .to_rfc2822();
 
+            let mut extra = Extras::new();
+            extra.insert("md5".to_string(), p.md5.clone().into());
+            extra.insert("tags".to_string(), p.tags.split(' ').collect::<Vec<_>>().into());
+            extras.insert(p.id.to_string(), extra);
+
             Ok(ItemBuilder::default()
                 .title(Some(title))
                 .guid(Some(GuidBuilder::default().value(p.id.to_string()).build()))
-                .link(Some(format!("https://gelbooru.com/index.php?page=post&s=view&id={}", p.id)))
+                .link(Some(format!("{HOST}/index.php?page=post&s=view&id={}", p.id)))
                 .pub_date(Some(pub_date))
                 .build())
         })
         .collect::<Result<Vec<_>>>()?;
 
-    db.flush()?;
+    let mut tags = query.iter().map(|q| urlencoding::encode(q)).collect::<Vec<_>>().join("+");
 
     if !matched_blacklist_tags.is_empty() {
         let mut blacklisted = matched_blacklist_tags.into_iter().collect::<Vec<_>>();
@@ -94,66 +166,49 @@ pub fn get(query: Vec<String>) -> Result<()> {
 
     let feed = ChannelBuilder::default()
         .title(query.join(", "))
-        .link(format!("https://gelbooru.com/index.php?page=post&s=list&tags={tags}"))
+        .link(format!("{HOST}/index.php?page=post&s=list&tags={tags}"))
         .description(query.join(", ") + " - Gelbooru")
         .ttl(Some(120.to_string()))
         .items(items)
         .build();
 
-    print!("{feed}");
-    Ok(())
+    Ok(Feed { channel: feed, extras, ..Feed::default() })
 }
 
-fn open_db() -> Result<DB> {
-    let mut opts = rocksdb::Options::default();
-    opts.create_if_missing(true);
-    opts.create_missing_column_families(true);
-    opts.set_compression_type(rocksdb::DBCompressionType::Lz4);
-    opts.set_max_open_files(100);
-    opts.set_keep_log_file_num(10);
-
-    let path = CONFIG.tag_db.as_ref().map_or_else(
-        || {
-            let mut p = dirs::home_dir().unwrap();
-            p.push(".rss");
-            p.push("geltagdb");
-            p
-        },
-        Into::into,
-    );
-
-    let Ok(db) = DB::open(&opts, &path) else {
-        DB::repair(&opts, &path)?;
-        return Ok(DB::open(&opts, &path)?);
-    };
-    Ok(db)
+// Opened for each feed and closed when it's done, so long batch or serve runs don't lock every
+// other gelbooru process out of it.
+pub(crate) fn tag_db() -> Result<DB> {
+    db::open(&tag_db_path()?)
 }
 
-fn add_api_params(url: &mut Url) {
+pub(crate) fn tag_db_path() -> Result<PathBuf> {
+    // The sample config used to suggest an empty string for the default
+    match config()?.tag_db.as_ref().filter(|p| !p.as_os_str().is_empty()) {
+        Some(path) => Ok(path.clone()),
+        None => db::default_path("geltagdb"),
+    }
+}
+
+fn add_api_params(url: &mut Url) -> Result<()> {
+    let config = config()?;
     let mut pairs = url.query_pairs_mut();
-    if let Some(user_id) = &CONFIG.user_id {
+    if let Some(user_id) = &config.user_id {
         pairs.append_pair("user_id", user_id);
     }
-    if let Some(api_key) = &CONFIG.api_key {
+    if let Some(api_key) = &config.api_key {
         pairs.append_pair("api_key", api_key);
     }
+    Ok(())
 }
 
-fn get_title_for_image(client: &Client, db: &DB, post: &Post, query: &[String]) -> Result<String> {
-    let mut relevant_tags = HashSet::new();
-
-    let missing_tags: Vec<_> = post
-        .tags
-        .split(' ')
-        .map(|t| (t, db.get(t)))
-        .filter_map(|(t, r)| match r {
-            Ok(Some(n)) if !n.is_empty() => {
-                if tag_in_title(t, n[0], query) {
-                    relevant_tags.insert(t);
-                }
-                None
-            }
-            Ok(_) => Some(Ok(t)),
+// Looks up every tag in the database, fetching any that are missing.
+fn tag_types(client: &Client, db: &DB, tags: &[&str]) -> Result<HashMap<String, u8>> {
+    let missing_tags: Vec<_> = tags
+        .iter()
+        .map(|t| db.get(t).map(|n| (*t, n)))
+        .filter_map(|r| match r {
+            Ok((_, Some(n))) if !n.is_empty() => None,
+            Ok((t, _)) => Some(Ok(t)),
             Err(e) => Some(Err(e.into())),
         })
         .collect::<Result<_>>()?;
@@ -161,26 +216,31 @@ fn get_title_for_image(client: &Client, db: &DB, post: &Post, query: &[String])
     // Fetch any missing tags
     missing_tags
         .chunks(50)
-        .map(|c| {
-            thread::sleep(DELAY);
-            load_missing_tags(client, db, c)
-        })
+        .map(|c| load_missing_tags(client, db, c))
         .collect::<Result<Vec<_>>>()?;
 
-    missing_tags
-        .into_iter()
-        .map(|t| (t, db.get(t)))
-        .filter_map(|(t, r)| match r {
-            Ok(Some(n)) if !n.is_empty() => {
-                if tag_in_title(t, n[0], query) {
-                    relevant_tags.insert(t);
-                }
-                None
-            }
-            Ok(_) => Some(Err(eyre!("Unable to read tag_type for {t}"))),
-            Err(e) => Some(Err(e.into())),
+    tags.iter()
+        .map(|t| match db.get(t)? {
+            Some(n) if !n.is_empty() => Ok((t.to_string(), n[0])),
+            _ => Err(Error::parse(format!("Unable to read tag_type for {t}"))),
+        })
+        .collect()
+}
+
+fn get_title_for_image(
+    tag_types: &HashMap<String, u8>,
+    post: &Post,
+    query: &[String],
+) -> Result<String> {
+    let relevant_tags = post
+        .tags
+        .split(' ')
+        .map(|t| match tag_types.get(t) {
+            Some(&tag_type) => Ok((t, tag_in_title(t, tag_type, query))),
+            None => Err(Error::parse(format!("Unable to read tag_type for {t}"))),
         })
-        .collect::<Result<()>>()?;
+        .filter_map(|r| r.map(|(t, relevant)| relevant.then_some(t)).transpose())
+        .collect::<Result<HashSet<_>>>()?;
 
 
     let mut title = if relevant_tags.is_empty() {
@@ -221,39 +281,63 @@ fn load_missing_tags(client: &Client, db: &DB, tags: &[&str]) -> Result<()> {
         .join("+");
 
     let mut tags_url = Url::parse(&format!(
-        "https://gelbooru.com/index.php?page=dapi&s=tag&q=index&json=1&names={escaped}"
+        "{}/index.php?page=dapi&s=tag&q=index&json=1&names={escaped}",
+        http::base_url("gelbooru", HOST)
     ))?;
-    add_api_params(&mut tags_url);
+    add_api_params(&mut tags_url)?;
 
-    let response = client.get(tags_url).send()?.bytes()?;
+    let response = http::send(client.get(tags_url))?.bytes()?;
 
-    let _span = error_span!("load_missing_tags", response = %String::from_utf8_lossy(&response));
+    for (name, tag_type) in parse_tags(tags, &response)? {
+        db.put(name, vec![tag_type])?;
+    }
+
+    Ok(())
+}
 
-    let response: TagsResponse = serde_json::from_slice(&response)?;
+/// Reads the types of `tags` from a tag index response.
+pub fn parse_tags(tags: &[&str], response: &[u8]) -> Result<Vec<(String, u8)>> {
+    let _span =
+        error_span!("load_missing_tags", response = %String::from_utf8_lossy(response)).entered();
+
+    let response: TagsResponse = serde_json::from_slice(response)?;
 
     // Some tags are duplicated, how. Why.
     // Response isn't in any particular order either.
     let mut unmatched: HashSet<&str> = tags.iter().copied().collect();
-    assert!(unmatched.len() == response.tag.len());
+    if unmatched.len() != response.tag.len() {
+        return Err(Error::parse(format!(
+            "Asked for {} tags but got {}",
+            unmatched.len(),
+            response.tag.len()
+        )));
+    }
 
+    let mut types = Vec::new();
 
     for tag in &response.tag {
-        db.put(&tag.name, vec![tag.type_field.try_into()?])?;
+        let tag_type: u8 = tag
+            .type_field
+            .try_into()
+            .map_err(|_| Error::parse(format!("Invalid tag type for {tag:?}")))?;
+        types.push((tag.name.clone(), tag_type));
 
         // Some tags are just different from different APIs. Fun.
         if !unmatched.remove(tag.name.as_str()) {
             let lower = tag.name.to_lowercase();
             if unmatched.remove(lower.as_str()) {
-                db.put(&lower, vec![tag.type_field.try_into()?])?
+                types.push((lower, tag_type));
             }
         }
     }
 
     if !unmatched.is_empty() {
-        bail!("Got unmatched tags {unmatched:?} in response {response:?}");
+        return Err(Error::parse(format!(
+            "Got unmatched tags {unmatched:?} in response {response:?}"
+        )));
     }
 
-    Ok(())
+    Ok(types)
 }
 
 #[derive(Default, Debug, Clone, Deserialize)]
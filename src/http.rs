@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{env, thread};
+
+use chrono::{DateTime, Utc};
+use reqwest::blocking::multipart::Form;
+use reqwest::blocking::{Request, Response};
+use reqwest::header::{
+    ETAG, HeaderMap, HeaderName, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    RETRY_AFTER,
+};
+use reqwest::{IntoUrl, Proxy, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use tracing::{Span, debug, field, info, info_span, warn};
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::throttle::{self, Limit};
+use crate::{cache, config, recording};
+
+// Longer waits than this fail the feed instead, aw-rss will try again later.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(5 * 60);
+
+struct Retries {
+    retries: u32,
+    backoff: Duration,
+}
+
+static RETRIES: LazyLock<Retries> = LazyLock::new(|| Retries {
+    retries: env::var("RSS_SCRAPERS_RETRIES").ok().and_then(|r| r.parse().ok()).unwrap_or(3),
+    backoff: env::var("RSS_SCRAPERS_BACKOFF")
+        .ok()
+        .and_then(|b| b.parse().ok())
+        .and_then(|b| Duration::try_from_secs_f64(b).ok())
+        .unwrap_or(Duration::from_secs(1)),
+});
+
+// Hosts that asked us to stop sending requests until a given time. Only mangadex sends the
+// X-RateLimit-* headers.
+static RATE_LIMITED: LazyLock<Mutex<HashMap<String, SystemTime>>> = LazyLock::new(Mutex::default);
+
+/// A scraper's client. Every request sent through it is rate limited and cached with the
+/// scraper's settings, whether or not the feed is fetched through [`crate::scrape`].
+#[derive(Clone)]
+pub(crate) struct Client {
+    inner: reqwest::blocking::Client,
+    scraper: &'static str,
+    delay: Option<Duration>,
+    cache: bool,
+}
+
+impl Client {
+    pub(crate) fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        RequestBuilder { inner: self.inner.get(url), client: self.clone(), any_status: false }
+    }
+
+    pub(crate) fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        RequestBuilder { inner: self.inner.post(url), client: self.clone(), any_status: false }
+    }
+}
+
+/// A request that hasn't been sent yet, see [`send`].
+pub(crate) struct RequestBuilder {
+    inner: reqwest::blocking::RequestBuilder,
+    client: Client,
+    any_status: bool,
+}
+
+impl RequestBuilder {
+    pub(crate) fn header<K, V>(self, name: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<::http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<::http::Error>,
+    {
+        Self { inner: self.inner.header(name, value), ..self }
+    }
+
+    pub(crate) fn multipart(self, form: Form) -> Self {
+        Self { inner: self.inner.multipart(form), ..self }
+    }
+
+    /// Returns error statuses that aren't retried instead of failing, for pages that are still
+    /// useful then.
+    pub(crate) fn any_status(self) -> Self {
+        Self { any_status: true, ..self }
+    }
+}
+
+pub(crate) struct ClientBuilder {
+    inner: reqwest::blocking::ClientBuilder,
+    scraper: &'static str,
+    delay: Option<Duration>,
+    cache: bool,
+}
+
+impl ClientBuilder {
+    /// The average time between requests to the site when none is configured. Without one the
+    /// scraper is only rate limited when a delay is configured.
+    pub(crate) fn delay(self, delay: Duration) -> Self {
+        Self { delay: Some(delay), ..self }
+    }
+
+    /// Also turns off the disk cache, since the responses depend on the session's cookies, which
+    /// are only added to requests as they're sent.
+    pub(crate) fn cookie_provider(self, cookies: Arc<CookieStoreMutex>) -> Self {
+        Self { inner: self.inner.cookie_provider(cookies), cache: false, ..self }
+    }
+
+    pub(crate) fn build(self) -> Result<Client> {
+        let Self { inner, scraper, delay, cache } = self;
+        Ok(Client { inner: inner.build()?, scraper, delay, cache })
+    }
+}
+
+/// The base for every client, so they all share the same settings, including the user agent,
+/// proxy, timeout and headers from `rss-scrapers.toml` and the scraper's `[sites]` table in it.
+///
+/// `user_agent` is sent instead of the global user agent, for sites that need their own.
+pub(crate) fn builder(scraper: &'static str, user_agent: Option<&str>) -> Result<ClientBuilder> {
+    let network = config::network(scraper, user_agent)?;
+    let mut builder = reqwest::blocking::Client::builder();
+
+    if let Some(user_agent) = &network.user_agent {
+        builder = builder.user_agent(user_agent);
+    }
+    if let Some(proxy) = &network.proxy {
+        builder = builder.proxy(
+            Proxy::all(proxy).map_err(|e| Error::config(format!("invalid proxy {proxy}: {e}")))?,
+        );
+    }
+    if let Some(timeout) = network.timeout {
+        builder = builder.timeout(timeout);
+    }
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &network.headers {
+        let invalid = |e: String| Error::config(format!("invalid header {name}: {e}"));
+        headers.insert(
+            HeaderName::try_from(name).map_err(|e| invalid(e.to_string()))?,
+            HeaderValue::try_from(value).map_err(|e| invalid(e.to_string()))?,
+        );
+    }
+
+    Ok(ClientBuilder { inner: builder.default_headers(headers), scraper, delay: None, cache: true })
+}
+
+pub(crate) fn client(scraper: &'static str) -> Result<Client> {
+    builder(scraper, None)?.build()
+}
+
+/// The base url for requests to a site, without a trailing slash.
+///
+/// `RSS_SCRAPERS_<SCRAPER>_URL`, like `RSS_SCRAPERS_SEASONAL_ANIME_URL`, overrides the default so
+/// scrapers can be pointed at a local server or a mirror. Links in the output still point to the
+/// real site.
+pub(crate) fn base_url(scraper: &str, default: &str) -> String {
+    let var = format!("RSS_SCRAPERS_{}_URL", scraper.to_uppercase().replace('-', "_"));
+    env::var(var).as_deref().unwrap_or(default).trim_end_matches('/').to_string()
+}
+
+/// Sends a request, retrying connection failures, 429s and 5xx errors with exponential backoff.
+///
+/// `Retry-After` is used instead of the backoff when the server sends it. Requests with bodies
+/// that can't be cloned, like multipart forms, are only attempted once. Any other status that
+/// isn't a success or a 304 is an error too, unless the request allows it with
+/// [`RequestBuilder::any_status`].
+///
+/// With `cache` on in `rss-scrapers.toml`, fresh responses are answered from the disk cache
+/// instead, see [`cache::slot`].
+///
+/// Each request is logged in a `request` span with its url, final status, latency and number of
+/// retries.
+pub(crate) fn send(req: RequestBuilder) -> Result<Response> {
+    let RequestBuilder { inner, client, any_status } = req;
+    let request = inner.build()?;
+
+    let span = info_span!(
+        "request",
+        method = %request.method(),
+        url = %request.url(),
+        status = field::Empty,
+        latency = field::Empty,
+        retries = 0,
+    )
+    .entered();
+    let start = Instant::now();
+
+    let slot = match client.cache {
+        true => cache::slot(client.scraper, &request)?,
+        false => None,
+    };
+    if let Some(slot) = &slot
+        && let Some(resp) = slot.get()?
+    {
+        span.record("status", resp.status().as_u16());
+        info!("cached");
+        return Ok(resp);
+    }
+
+    let limit = config::rate_limit(client.scraper, client.delay)?;
+    let result = send_with_retries(&client.inner, request, limit);
+
+    span.record("latency", field::debug(start.elapsed()));
+    let resp = match result {
+        Ok(resp) => resp,
+        Err(e) => {
+            debug!("failed: {e}");
+            return Err(e);
+        }
+    };
+
+    span.record("status", resp.status().as_u16());
+    info!("finished");
+
+    // Like a 404 for something deleted or a Cloudflare challenge, which the parsers would only
+    // mistake for the site changing
+    let unexpected =
+        !any_status && !resp.status().is_success() && resp.status() != StatusCode::NOT_MODIFIED;
+    if retryable(resp.status()) || unexpected {
+        return Err(ErrorKind::Status(resp.status()).into());
+    }
+    match slot {
+        Some(slot) => slot.put(resp),
+        None => Ok(resp),
+    }
+}
+
+fn send_with_retries(
+    client: &reqwest::blocking::Client,
+    request: Request,
+    limit: Option<Limit>,
+) -> Result<Response> {
+    let host = request.url().host_str().unwrap_or_default().to_string();
+    let Retries { retries, backoff } = *RETRIES;
+
+    for attempt in 0..retries {
+        let Some(req) = request.try_clone() else {
+            break;
+        };
+
+        wait_for_rate_limit(&host, limit)?;
+
+        let retry_after = match recording::execute(client, req)? {
+            Ok(resp) => {
+                record_rate_limit(&host, &resp);
+                if !retryable(resp.status()) {
+                    return Ok(resp);
+                }
+                match retry_after(&resp) {
+                    Some(wait) if wait > MAX_RETRY_WAIT => return Ok(resp),
+                    wait => wait,
+                }
+            }
+            Err(e) if e.is_connect() || e.is_timeout() || e.is_request() => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let wait = retry_after
+            .unwrap_or_else(|| {
+                backoff.checked_mul(2u32.saturating_pow(attempt)).unwrap_or(MAX_RETRY_WAIT)
+            })
+            .min(MAX_RETRY_WAIT);
+        warn!("Request failed, retrying in {wait:?}");
+        thread::sleep(wait);
+        Span::current().record("retries", attempt + 1);
+    }
+
+    wait_for_rate_limit(&host, limit)?;
+    let resp = recording::execute(client, request)??;
+    record_rate_limit(&host, &resp);
+    Ok(resp)
+}
+
+fn retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// Either a number of seconds or an http date
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let value = resp.headers().get(RETRY_AFTER)?.to_str().ok()?;
+
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.to_utc() - Utc::now()).to_std().ok()
+}
+
+fn record_rate_limit(host: &str, resp: &Response) {
+    let header = |name| resp.headers().get(name).and_then(|h| h.to_str().ok());
+
+    if resp.status() != StatusCode::TOO_MANY_REQUESTS
+        && header("X-RateLimit-Remaining") != Some("0")
+    {
+        return;
+    }
+
+    // A unix timestamp, not a number of seconds like Retry-After
+    let Some(until) = header("X-RateLimit-Retry-After").and_then(|t| t.parse().ok()) else {
+        return;
+    };
+
+    RATE_LIMITED
+        .lock()
+        .unwrap()
+        .insert(host.to_string(), UNIX_EPOCH + Duration::from_secs(until));
+}
+
+// Waits out the site's own rate limit, then takes a token from the scraper's bucket for the host,
+// which is the mock server's when the url is overridden. Replayed requests never reach the site.
+fn wait_for_rate_limit(host: &str, limit: Option<Limit>) -> Result<()> {
+    let until = RATE_LIMITED.lock().unwrap().remove(host);
+
+    if let Some(Ok(wait)) = until.map(|u| u.duration_since(SystemTime::now())) {
+        warn!("Rate limited by {host}, waiting {wait:?}");
+        thread::sleep(wait.min(MAX_RETRY_WAIT));
+    }
+
+    match limit {
+        Some(limit) if !recording::is_replaying() => throttle::wait(host, limit),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) struct Modified {
+    pub response: Response,
+    /// The etag to pass back through --etag next time.
+    pub etag: Option<String>,
+}
+
+/// Sends a request made conditional on the etag from the previous run, returning None if the
+/// server says nothing changed.
+///
+/// aw-rss only stores a single etag per feed, so the "etag" is the ETag header when the site
+/// sends one and the Last-Modified date otherwise.
+pub(crate) fn conditional(mut req: RequestBuilder, etag: Option<&str>) -> Result<Option<Modified>> {
+    match etag {
+        Some(date) if DateTime::parse_from_rfc2822(date).is_ok() => {
+            req = req.header(IF_MODIFIED_SINCE, date);
+        }
+        Some(etag) => req = req.header(IF_NONE_MATCH, etag),
+        None => {}
+    }
+
+    let response = send(req)?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    let etag = etag_of(&response);
+    Ok(Some(Modified { response, etag }))
+}
+
+pub(crate) fn etag_of(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(ETAG)
+        .or_else(|| response.headers().get(LAST_MODIFIED))
+        .and_then(|etag| etag.to_str().ok())
+        .map(ToString::to_string)
+}
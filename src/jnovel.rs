@@ -1,26 +1,78 @@
 use std::collections::HashSet;
 
-use chrono::Utc;
-use color_eyre::Result;
-use reqwest::blocking::Client;
-use rss::{ChannelBuilder, GuidBuilder, Item, ItemBuilder};
+use clap::Args;
+use rss::{Channel, ChannelBuilder, GuidBuilder, Item, ItemBuilder};
 use serde::Deserialize;
 use tracing::error_span;
 
-// This might take etags, but I'm not sure I trust them
-pub fn get(series: String /* , etag: Option<String> */) -> Result<()> {
-    let client = reqwest::blocking::Client::new();
+use crate::error::Result;
+use crate::http::{self, Modified};
+use crate::scraper::{Feed, Fetched, Scraper};
 
-    let response = client
-        .get(format!(r#"https://api.j-novel.club/api/series/findOne?filter={{"where":{{"titleslug":"{series}"}},"include":["volumes","parts"]}}"#))
-        .send()?.bytes()?;
+const API: &str = "https://api.j-novel.club";
 
-    let _span = error_span!("response", response = &*String::from_utf8_lossy(&response)).entered();
-    let info: SeriesInfo = serde_json::from_slice(&response)?;
+#[derive(Debug, Clone, Args)]
+pub struct Jnovel {
+    /// The jnovel title slug, from after /series/ in the title.
+    /// https://j-novel.club/series/ab-cd has a title slug of ab-cd
+    #[arg(allow_hyphen_values = true)]
+    pub title_slug: String,
+}
+
+impl Scraper for Jnovel {
+    fn name(&self) -> &'static str {
+        "jnovel"
+    }
+
+    fn feed(&self) -> String {
+        self.title_slug.clone()
+    }
+
+    fn fetch(&self, etag: Option<&str>) -> Result<Fetched> {
+        get(&self.title_slug, etag)
+    }
+}
 
-    let finals = final_chapters(&client, &info.id)?;
+// Only the series request is conditional. A part being marked final without any new parts
+// will be missed until the next change to the series.
+fn get(series: &str, etag: Option<&str>) -> Result<Fetched> {
+    let client = http::client("jnovel")?;
+
+    let api = http::base_url("jnovel", API);
+    let req = client
+        .get(format!(r#"{api}/api/series/findOne?filter={{"where":{{"titleslug":"{series}"}},"include":["volumes","parts"]}}"#));
+    let Some(Modified { response, etag }) = http::conditional(req, etag)? else {
+        return Ok(Fetched::NotModified);
+    };
+    let response = response.bytes()?;
+
+    let feed = parse(series, &response, |id| {
+        Ok(http::send(
+            client.get(format!("{api}/api/events?filter[limit]=100&filter[where][serieId]={id}")),
+        )?
+        .bytes()?
+        .into())
+    })?;
+
+    Ok(Feed { channel: feed, etag, ..Feed::default() }.into())
+}
+
+/// Builds the feed from the series info. `events` fetches the series' recent events, which are
+/// the only place that final parts are marked.
+pub fn parse(
+    series: &str,
+    info: &[u8],
+    events: impl FnOnce(&str) -> Result<Vec<u8>>,
+) -> Result<Channel> {
+    let span = error_span!("response", response = &*String::from_utf8_lossy(info)).entered();
+    let info: SeriesInfo = serde_json::from_slice(info)?;
+
+    // Keep the whole response out of the logs for the next request
+    let span = span.exit();
+    let events = events(&info.id)?;
+    let _span = span.entered();
 
-    let now = Utc::now().to_rfc2822();
+    let finals = final_chapters(&events)?;
 
     let items: Vec<Item> = info
         .parts
@@ -38,35 +90,24 @@ pub fn get(series: String /* , etag: Option<String> */) -> Result<()> {
                 .title(Some(title))
                 .link(Some(format!("https://j-novel.club{fragment}")))
                 .guid(Some(GuidBuilder::default().value(p.titleslug).build()))
-                .pub_date(Some(now.clone()))
                 .build()
         })
         .collect();
 
 
-    let feed = ChannelBuilder::default()
+    Ok(ChannelBuilder::default()
         .title(info.title)
         .link(format!("https://j-novel.club/series/{series}"))
         .description(info.description_short)
         .ttl(Some("60".into()))
         .items(items)
-        .build();
-
-    print!("{feed}");
-    Ok(())
+        .build())
 }
 
-fn final_chapters(client: &Client, id: &str) -> Result<HashSet<String>> {
-    let response = client
-        .get(format!(
-            "https://api.j-novel.club/api/events?filter[limit]=100&filter[where][serieId]={id}"
-        ))
-        .send()?
-        .bytes()?;
-
+fn final_chapters(response: &[u8]) -> Result<HashSet<String>> {
     let _span =
-        error_span!("final_chapters", response = &*String::from_utf8_lossy(&response)).entered();
-    let events: Vec<Event> = serde_json::from_slice(&response)?;
+        error_span!("final_chapters", response = &*String::from_utf8_lossy(response)).entered();
+    let events: Vec<Event> = serde_json::from_slice(response)?;
 
     Ok(events
         .into_iter()
@@ -0,0 +1,38 @@
+#![allow(rustdoc::bare_urls)]
+//! Scrapers that build rss feeds for sites that do not provide their own.
+//!
+//! Each scraper module exposes a struct holding its arguments, the same ones the
+//! `rss-scrapers` subcommands take, that implements [`Scraper`]. Pass it to [`scrape`] to get
+//! the feed the subcommand would print.
+
+pub mod ao3;
+pub mod batch;
+mod cache;
+mod command;
+pub mod config;
+mod db;
+pub mod doctor;
+mod error;
+mod filter;
+mod first_seen;
+pub mod gelbooru;
+mod http;
+pub mod jnovel;
+pub mod mangadex;
+pub mod migrate;
+pub mod opml;
+pub mod output;
+pub mod qq;
+pub mod recording;
+pub mod royalroad;
+mod scraper;
+pub mod seasonal_anime;
+pub mod serve;
+pub mod tfgames;
+mod throttle;
+pub mod validate;
+pub mod vn_news;
+
+pub use command::ScraperCommand;
+pub use error::{Category, Error, ErrorKind, Result};
+pub use scraper::{Extras, Feed, Fetched, Scraper, scrape};
@@ -1,98 +1,312 @@
-#![allow(rustdoc::bare_urls)]
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::{error, fs, io, iter};
 
-use clap::Parser;
-use color_eyre::Result;
-use tracing_error::ErrorLayer;
+use clap::{Parser, ValueEnum};
+use color_eyre::eyre::{bail, eyre};
+use color_eyre::{Report, Result};
+use rss::Channel;
+use rss_scrapers::doctor::{self, Status};
+use rss_scrapers::output::{self, Format};
+use rss_scrapers::{
+    Category, Error, Fetched, ScraperCommand, batch, config, migrate, opml, recording, serve,
+    validate,
+};
+use serde_json::json;
+use tracing_error::{ErrorLayer, ExtractSpanTrace};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::layer::SubscriberExt;
-
-mod ao3;
-mod gelbooru;
-mod jnovel;
-mod mangadex;
-mod qq;
-mod royalroad;
-mod seasonal_anime;
-mod tfgames;
-mod vn_news;
+use tracing_subscriber::{EnvFilter, Layer};
 
 #[derive(Debug, Parser)]
-#[clap(
-    name = "rss-scrapers",
-    about = "Tool for scraping various sites and constructing rss feeds"
-)]
+#[clap(name = "rss-scrapers", about = "Tool for scraping various sites and constructing rss feeds")]
 pub struct Opt {
     #[command(subcommand)]
     cmd: Command,
 
     #[arg(long, global = true)]
     etag: Option<String>,
+
+    /// Defaults to the format in rss-scrapers.toml, or rss
+    #[arg(long, global = true, value_enum)]
+    format: Option<Format>,
+
+    /// Save every request and response to this directory
+    #[arg(long, global = true, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Answer requests from responses saved with --record instead of the network
+    #[arg(long, global = true, value_name = "DIR")]
+    replay: Option<PathBuf>,
+
+    /// Write the body of every response to its own file in this directory
+    #[arg(long, global = true, value_name = "DIR")]
+    dump_responses: Option<PathBuf>,
+
+    /// Write logs to this file, rotated daily, instead of stderr. RUST_LOG sets the level
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// How to print the error to stderr when the command fails
+    #[arg(long, global = true, value_enum, default_value_t)]
+    error_format: ErrorFormat,
+}
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+enum ErrorFormat {
+    #[default]
+    Text,
+    /// A single JSON object with the category, exit code and causes
+    Json,
 }
 
 #[derive(Debug, Parser)]
-enum Command {
-    /// Archive Of Our Own
-    Ao3 {
-        /// The story id from the URL.
-        /// https://archiveofourown.org/works/1234 has a story id of 1234
-        #[arg(allow_hyphen_values = true)]
-        story_id: String,
+enum OpmlCommand {
+    /// Print the feeds in a batch manifest or aw-rss database as OPML
+    Export {
+        #[arg(long, required_unless_present = "aw_rss", conflicts_with = "aw_rss")]
+        manifest: Option<PathBuf>,
+        /// An aw-rss database, only its rss-scrapers feeds are exported
+        #[arg(long, value_name = "DB")]
+        aw_rss: Option<PathBuf>,
+        /// Use urls under a `serve` server instead of aw-rss commands
+        #[arg(long, value_name = "URL")]
+        serve_url: Option<String>,
     },
-    /// Gelbooru Rss
-    /// Uses $HOME/.rss/geltagblacklist
-    Gelbooru {
-        #[arg(allow_hyphen_values = true, required=true, num_args=1..)]
-        query: Vec<String>,
+    /// Print a batch manifest for the rss-scrapers feeds in an OPML file
+    Import {
+        opml: PathBuf,
+        /// Where the manifest writes each feed
+        #[arg(long, value_name = "DIR")]
+        output_dir: PathBuf,
+        /// Also import feeds with urls under a `serve` server
+        #[arg(long, value_name = "URL")]
+        serve_url: Option<String>,
     },
-    /// Jnovel-club series
-    Jnovel {
-        /// The jnovel title slug, from after /series/ in the title.
-        /// https://j-novel.club/series/ab-cd has a title slug of ab-cd
-        #[arg(allow_hyphen_values = true)]
-        title_slug: String,
+}
+
+#[derive(Debug, Parser)]
+enum Command {
+    #[command(flatten)]
+    Scraper(ScraperCommand),
+    /// Scrape every feed in a TOML manifest, writing each to its own file
+    Batch { manifest: PathBuf },
+    /// Serve feeds over http, where /mangadex/<uuid> is the same as `mangadex <uuid>`
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
     },
-    /// Mangadex series
-    Mangadex {
-        /// Mangadex series UUID
-        /// https://mangadex.org/title/975f3334-8395-4393-84a2-50fcaccbcdc0 has a UUID of
-        /// 975f3334-8395-4393-84a2-50fcaccbcdc0
-        #[arg(allow_hyphen_values = true)]
-        series: String,
+    /// Check every config, the QQ login and the databases without scraping any feeds
+    Doctor,
+    /// Check a scraper's feed, or an RSS feed read from stdin, for problems like duplicate guids
+    Validate {
+        #[command(subcommand)]
+        scraper: Option<ScraperCommand>,
     },
-    // QQ
-    QQ {
-        /// Thread ID
-        /// /threads/ab-cd.1234 has an ID of ab-cd.1234
-        #[arg(allow_hyphen_values = true)]
-        thread_id: String,
+    /// Convert between OPML and batch manifests or aw-rss feed lists
+    Opml {
+        #[command(subcommand)]
+        cmd: OpmlCommand,
     },
-    RoyalRoad,
-    SeasonalAnime,
-    Tfgames {
-        /// Game ID
-        /// https://tfgames.site/?module=viewgame&id=1234 has an ID of 1234
-        #[arg(allow_hyphen_values = true)]
-        game_id: String,
+    /// Update feeds and items in an aw-rss database that use old commands or guids
+    MigrateAwRss {
+        db: PathBuf,
+        /// Print the changes without making them
+        #[arg(long)]
+        dry_run: bool,
     },
-    VnNews,
 }
 
+// Each category of error exits with its own code, see the README.
+fn main() -> ExitCode {
+    let opt = Opt::parse();
+    let error_format = opt.error_format;
+
+    let Err(report) = run(opt) else {
+        return ExitCode::SUCCESS;
+    };
 
-fn main() -> Result<()> {
+    let category = report.downcast_ref::<Error>().map_or(Category::Other, Error::category);
+
+    match error_format {
+        ErrorFormat::Text => eprintln!("Error: {report:?}"),
+        ErrorFormat::Json => {
+            let error = json!({
+                "category": category.name(),
+                "exit_code": category.exit_code(),
+                "transient": category.is_transient(),
+                "message": report.to_string(),
+                "causes": causes(report.chain().skip(1)),
+            });
+            eprintln!("{error}");
+        }
+    }
+
+    ExitCode::from(category.exit_code())
+}
+
+fn run(opt: Opt) -> Result<()> {
     color_eyre::install()?;
-    let subscriber = tracing_subscriber::Registry::default().with(ErrorLayer::default());
+
+    // Logs go to stderr, or a file rotated daily, so they never mix with the feed on stdout.
+    let writer = match &opt.log_file {
+        Some(path) => {
+            let dir = path.parent().unwrap_or(Path::new("."));
+            let prefix = path.file_name().ok_or_else(|| eyre!("Invalid log file {path:?}"))?;
+            BoxMakeWriter::new(tracing_appender::rolling::daily(dir, prefix))
+        }
+        None => BoxMakeWriter::new(io::stderr),
+    };
+    let log = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(opt.log_file.is_none())
+        .with_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::WARN.into())
+                .from_env()?,
+        );
+
+    let subscriber = tracing_subscriber::Registry::default().with(ErrorLayer::default()).with(log);
     tracing::subscriber::set_global_default(subscriber)?;
 
-    let opt = Opt::parse();
+    if let Some(dir) = &opt.record {
+        recording::record(dir)?;
+    }
+    if let Some(dir) = &opt.replay {
+        recording::replay(dir)?;
+    }
+    if let Some(dir) = &opt.dump_responses {
+        recording::dump(dir)?;
+    }
+
+    // Only read the config for commands that render feeds, so doctor can report a broken one
+    let format = || -> Result<Format> {
+        Ok(match opt.format {
+            Some(format) => format,
+            None => config::get()?.format.unwrap_or_default(),
+        })
+    };
+
+    let cmd = match opt.cmd {
+        Command::Scraper(cmd) => cmd,
+        Command::Batch { manifest } => return run_batch(&manifest, format()?),
+        Command::Serve { address } => return Ok(serve::run(&address, format()?)?),
+        Command::Doctor => return run_doctor(),
+        Command::Opml { cmd } => return run_opml(cmd),
+        Command::Validate { scraper } => return run_validate(scraper),
+        Command::MigrateAwRss { db, dry_run } => return run_migrate(&db, dry_run),
+    };
+
+    let scraper = cmd.into_scraper();
+
+    match rss_scrapers::scrape(&*scraper, opt.etag.as_deref())? {
+        Fetched::NotModified => println!("not modified"),
+        Fetched::Feed(feed) => print!("{}", output::render(*feed, format()?)),
+    }
+
+    Ok(())
+}
+
+// The span trace holds whole response bodies, so it's only printed in full text errors.
+fn causes<'a>(chain: impl Iterator<Item = &'a (dyn error::Error + 'static)>) -> Vec<String> {
+    chain.filter(|e| e.span_trace().is_none()).map(ToString::to_string).collect()
+}
+
+fn run_doctor() -> Result<()> {
+    let checks = doctor::run();
+
+    for check in &checks {
+        match &check.status {
+            Status::Pass(msg) => println!("PASS  {}: {msg}", check.name),
+            Status::Skip(msg) => println!("SKIP  {}: {msg}", check.name),
+            Status::Fail(e) => {
+                let causes = causes(iter::successors(Some(e as &dyn error::Error), |e| e.source()));
+                println!("FAIL  {}: {}", check.name, causes.join(": "));
+            }
+        }
+    }
+
+    let failed = checks.iter().filter(|c| matches!(c.status, Status::Fail(_))).count();
+    if failed != 0 {
+        bail!("{failed} of {} checks failed", checks.len());
+    }
+    Ok(())
+}
+
+fn run_validate(scraper: Option<ScraperCommand>) -> Result<()> {
+    // Scraped feeds are rendered first so the XML itself is checked too
+    let xml = match scraper {
+        Some(cmd) => match rss_scrapers::scrape(&*cmd.into_scraper(), None)? {
+            Fetched::Feed(feed) => output::render(*feed, Format::Rss),
+            Fetched::NotModified => bail!("Unexpected not modified response"),
+        },
+        None => io::read_to_string(io::stdin())?,
+    };
+
+    let channel = Channel::read_from(xml.as_bytes())?;
+    let violations = validate::check(&channel);
+
+    for v in &violations {
+        println!("{v}");
+    }
+
+    if !violations.is_empty() {
+        bail!("{} problems in the feed", violations.len());
+    }
+    eprintln!("{} items, no problems", channel.items().len());
+    Ok(())
+}
+
+fn run_opml(cmd: OpmlCommand) -> Result<()> {
+    match cmd {
+        OpmlCommand::Export { manifest, aw_rss, serve_url } => {
+            let source = match (&manifest, &aw_rss) {
+                (Some(manifest), _) => opml::Source::Manifest(manifest),
+                (None, Some(db)) => opml::Source::AwRss(db),
+                (None, None) => unreachable!(),
+            };
+            print!("{}", opml::export(source, serve_url.as_deref())?);
+        }
+        OpmlCommand::Import { opml, output_dir, serve_url } => {
+            let opml = fs::read_to_string(opml)?;
+            print!("{}", opml::import(&opml, &output_dir, serve_url.as_deref())?);
+        }
+    }
+    Ok(())
+}
+
+fn run_migrate(db: &Path, dry_run: bool) -> Result<()> {
+    let changes = migrate::run(db, dry_run)?;
+
+    for change in &changes {
+        println!("{} ({})", change.migration, change.table);
+        println!("-{}", change.old);
+        println!("+{}", change.new);
+    }
+
+    if dry_run {
+        eprintln!("Would change {} rows", changes.len());
+    } else {
+        eprintln!("Changed {} rows", changes.len());
+    }
+    Ok(())
+}
+
+fn run_batch(manifest: &Path, format: Format) -> Result<()> {
+    let summary = batch::run(manifest, format)?;
+
+    let failed = summary.failed.len();
+    let total = summary.succeeded.len() + failed;
+
+    for (output, e) in summary.failed {
+        eprintln!("Failed to write {}: {:?}", output.display(), Report::from(e));
+    }
+
+    eprintln!("Wrote {} of {total} feeds", summary.succeeded.len());
 
-    match opt.cmd {
-        Command::Ao3 { story_id } => ao3::get(story_id),
-        Command::Gelbooru { query } => gelbooru::get(query),
-        Command::Jnovel { title_slug } => jnovel::get(title_slug),
-        Command::Mangadex { series } => mangadex::get(series),
-        Command::QQ { thread_id } => qq::get(thread_id, opt.etag),
-        Command::RoyalRoad => royalroad::get(),
-        Command::SeasonalAnime => seasonal_anime::get(),
-        Command::Tfgames { game_id } => tfgames::get(game_id),
-        Command::VnNews => vn_news::get(),
+    if failed != 0 {
+        bail!("{failed} of {total} feeds failed");
     }
+    Ok(())
 }
@@ -1,23 +1,29 @@
 use std::collections::{HashMap, HashSet};
-use std::thread;
+use std::sync::OnceLock;
 use std::time::Duration;
 
 use chrono::DateTime;
-use color_eyre::Result;
-use color_eyre::eyre::bail;
+use clap::Args;
 use reqwest::Url;
-use reqwest::blocking::Client;
 use rss::{ChannelBuilder, GuidBuilder, Item, ItemBuilder};
 use serde::Deserialize;
 use serde_with::{DefaultOnNull, NoneAsEmptyString, serde_as};
 use tracing::error_span;
 
+use crate::config;
+use crate::error::{Error, Result};
+use crate::http::{self, Modified};
+use crate::scraper::{Extras, Feed, Fetched, Scraper};
+
 const DELAY: Duration = Duration::from_secs(2);
 
+// mangadex asks clients not to pretend to be browsers, so this is used over the global user agent
 static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
 const PAGE_SIZE: usize = 100;
 
+const API: &str = "https://api.mangadex.org";
+
 
 #[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -27,141 +33,218 @@ struct MangaSyncerConfig {
     blocked_groups: Vec<String>,
 }
 
-pub fn get(series: String) -> Result<()> {
-    let client = Client::builder().user_agent(USER_AGENT).build()?;
+// The [mangadex] section of rss-scrapers.toml
+#[derive(Default, Debug, Deserialize)]
+struct Config {
+    #[serde(default)]
+    blocked_groups: Vec<String>,
+}
 
-    thread::sleep(DELAY);
+#[derive(Debug, Clone, Args)]
+pub struct Mangadex {
+    /// Mangadex series UUID
+    /// https://mangadex.org/title/975f3334-8395-4393-84a2-50fcaccbcdc0 has a UUID of
+    /// 975f3334-8395-4393-84a2-50fcaccbcdc0
+    #[arg(allow_hyphen_values = true)]
+    pub series: String,
+}
+
+impl Scraper for Mangadex {
+    fn name(&self) -> &'static str {
+        "mangadex"
+    }
 
-    let url = format!("https://api.mangadex.org/manga/{series}");
+    fn feed(&self) -> String {
+        self.series.clone()
+    }
+
+    fn fetch(&self, etag: Option<&str>) -> Result<Fetched> {
+        get(&self.series, etag)
+    }
+}
+
+// Only the manga info request is conditional. Changes to blocked groups or filters won't show up
+// until the next new chapter changes the manga info.
+fn get(series: &str, etag: Option<&str>) -> Result<Fetched> {
+    let client = http::builder("mangadex", Some(USER_AGENT))?.delay(DELAY).build()?;
+
+    let url = format!("{}/manga/{series}", http::base_url("mangadex", API));
 
     let _span = error_span!("manga_info", url = %url).entered();
 
-    let response = client.get(url).send()?.bytes()?;
+    // The manga info includes latestUploadedChapter, so it changes whenever the feed would.
+    let Some(Modified { response, etag }) = http::conditional(client.get(url), etag)? else {
+        return Ok(Fetched::NotModified);
+    };
+    let response = response.bytes()?;
+
+    let page_url = Url::parse(&format!("{}/manga/{series}/feed", http::base_url("mangadex", API)))?;
+
+    let feed = parse(series, &response, blocked_groups()?, |offset| {
+        let mut url = page_url.clone();
+        url.query_pairs_mut()
+            .append_pair("limit", &PAGE_SIZE.to_string())
+            .append_pair("translatedLanguage[]", "en")
+            .append_pair("order[chapter]", "desc")
+            .append_pair("offset", &offset.to_string());
+
+        let _span = error_span!("chapter_list", url = %url).entered();
+
+        Ok(http::send(client.get(url))?.bytes()?.into())
+    })?;
+
+    Ok(Feed { etag, ..feed }.into())
+}
 
-    let _span = error_span!("manga_info", response = %String::from_utf8_lossy(&response)).entered();
-    let info: MangaInfo = serde_json::from_slice(&response)?;
+/// Builds the feed from the manga info. `page` fetches the next 100 English chapters, newest
+/// first, starting at the given offset.
+pub fn parse(
+    series: &str,
+    info: &[u8],
+    blocked_groups: &HashSet<String>,
+    page: impl FnMut(usize) -> Result<Vec<u8>>,
+) -> Result<Feed> {
+    let span = error_span!("manga_info", response = %String::from_utf8_lossy(info)).entered();
+    let info: MangaInfo = serde_json::from_slice(info)?;
 
     if info.result != "ok" {
-        bail!("Failed to get info for {series}: {info:?}");
+        return Err(Error::parse(format!("Failed to get info for {series}: {info:?}")));
     }
 
     let title = english_or_first(&info.data.attributes.title).unwrap_or_default();
     let description = english_or_first(&info.data.attributes.description).unwrap_or_default();
 
 
+    // Keep the whole response out of the logs for the chapter requests
+    drop(span);
+    let (chapters, extras) = parse_chapters(series, &title, blocked_groups, page)?;
+
     let feed = ChannelBuilder::default()
         .description(description)
         .link(format!("https://mangadex.org/title/{series}"))
         .ttl(Some("60".into()))
-        .items(get_chapters(&client, &series, &title)?)
+        .items(chapters)
         .title(title)
         .build();
 
-    println!("{feed}");
-    Ok(())
+    Ok(Feed { channel: feed, extras, ..Feed::default() })
 }
 
 
-fn get_chapters(client: &Client, series: &str, title: &str) -> Result<Vec<Item>> {
+// Loaded once per process so batch runs don't reread the config for every series.
+pub(crate) fn blocked_groups() -> Result<&'static HashSet<String>> {
+    static BLOCKED_GROUPS: OnceLock<HashSet<String>> = OnceLock::new();
+
+    if let Some(groups) = BLOCKED_GROUPS.get() {
+        return Ok(groups);
+    }
+
     let manga_syncer_config: MangaSyncerConfig =
-        awconf::load_config("manga-syncer", None::<&str>, Some(""))?.0;
-    let blocked_groups: HashSet<_> = manga_syncer_config.blocked_groups.into_iter().collect();
+        awconf::load_config("manga-syncer", None::<&str>, Some(""))
+            .map_err(Error::config)?
+            .0;
+    let own_config: Config = config::table("mangadex")?.unwrap_or_default();
+
+    let groups = manga_syncer_config
+        .blocked_groups
+        .into_iter()
+        .chain(own_config.blocked_groups)
+        .collect();
+    Ok(BLOCKED_GROUPS.get_or_init(|| groups))
+}
 
+fn parse_chapters(
+    series: &str,
+    title: &str,
+    blocked_groups: &HashSet<String>,
+    mut page: impl FnMut(usize) -> Result<Vec<u8>>,
+) -> Result<(Vec<Item>, HashMap<String, Extras>)> {
     let mut total = 1;
     let mut offset = 0;
 
-    let mut page_url = Url::parse(&format!("https://api.mangadex.org/manga/{series}/feed"))?;
-
-    page_url
-        .query_pairs_mut()
-        .append_pair("limit", &PAGE_SIZE.to_string())
-        .append_pair("translatedLanguage[]", "en")
-        .append_pair("order[chapter]", "desc");
-
     let mut chapters = Vec::new();
+    let mut extras = HashMap::new();
 
     while offset < total {
-        thread::sleep(DELAY);
-
-        let mut url = page_url.clone();
-        url.query_pairs_mut().append_pair("offset", &offset.to_string());
-
-        let _span = error_span!("chapter_list", url = %url).entered();
-
-        let response = client.get(url).send()?.bytes()?;
+        let response = page(offset)?;
 
         let _span =
             error_span!("chapter_list", response = %String::from_utf8_lossy(&response)).entered();
 
-        println!("{}", String::from_utf8_lossy(&response));
-        let page: ChapterList = serde_json::from_slice(&response)?;
+        let list: ChapterList = serde_json::from_slice(&response)?;
 
 
-        total = page.total as usize;
-        if page.data.len() != PAGE_SIZE && offset + page.data.len() < total {
-            bail!(
+        total = list.total as usize;
+        if list.data.len() != PAGE_SIZE && offset + list.data.len() < total {
+            return Err(Error::parse(format!(
                 "Manga {series}: invalid chapter pagination. Requested {PAGE_SIZE} chapters at \
                  offset {offset} with {total} total but got {}",
-                page.data.len()
-            );
+                list.data.len()
+            )));
         }
 
-        chapters.extend(
-            page.data
-                .into_iter()
-                .filter(|c| {
-                    !c.relationships.iter().any(|r| {
-                        r.type_field == "scanlation_group" && blocked_groups.contains(&r.id)
-                    })
-                })
-                .map(|c| {
-                    let mut title =
-                        match (c.attributes.volume, c.attributes.chapter, c.attributes.title) {
-                            (Some(v), Some(c), Some(t)) => {
-                                format!("{title} - Volume {v}, Chapter {c} - {t}")
-                            }
-                            (Some(v), Some(c), None) => {
-                                format!("{title} - Volume {v}, Chapter {c}")
-                            }
-                            (None, Some(c), Some(t)) => {
-                                format!("{title} - Chapter {c} - {t}")
-                            }
-                            (None, Some(c), None) => {
-                                format!("{title} - Chapter {c}")
-                            }
-                            (None, None, Some(t)) => {
-                                format!("{title} - {t}")
-                            }
-                            (..) => format!("{title} -- unknown chapter"),
-                        };
-
-                    if c.attributes.pages == 0
-                        && c.attributes.external_url.is_some_and(|s| !s.is_empty())
-                    {
-                        title += " (External)";
-                    }
-
-                    // This is probably unnecessary (aw-rss will consume rfc3339) but matches the
-                    // old Go code exactly
-                    let pub_date = DateTime::parse_from_rfc3339(&c.attributes.created_at)
-                        .unwrap()
-                        .to_utc()
-                        .to_rfc2822();
-
-                    ItemBuilder::default()
-                        .title(Some(title))
-                        .link(Some(format!("https://mangadex.org/chapter/{}", c.id)))
-                        .guid(Some(GuidBuilder::default().value(c.id).build()))
-                        .pub_date(Some(pub_date))
-                        .build()
-                }),
-        );
+        let items = list
+            .data
+            .into_iter()
+            .filter(|c| {
+                !c.relationships
+                    .iter()
+                    .any(|r| r.type_field == "scanlation_group" && blocked_groups.contains(&r.id))
+            })
+            .map(|c| {
+                let mut extra = Extras::new();
+                extra.insert("volume".to_string(), c.attributes.volume.clone().into());
+                extra.insert("chapter".to_string(), c.attributes.chapter.clone().into());
+                extra.insert("title".to_string(), c.attributes.title.clone().into());
+                extra.insert("pages".to_string(), c.attributes.pages.into());
+                extras.insert(c.id.clone(), extra);
+
+                let mut title =
+                    match (c.attributes.volume, c.attributes.chapter, c.attributes.title) {
+                        (Some(v), Some(c), Some(t)) => {
+                            format!("{title} - Volume {v}, Chapter {c} - {t}")
+                        }
+                        (Some(v), Some(c), None) => {
+                            format!("{title} - Volume {v}, Chapter {c}")
+                        }
+                        (None, Some(c), Some(t)) => {
+                            format!("{title} - Chapter {c} - {t}")
+                        }
+                        (None, Some(c), None) => {
+                            format!("{title} - Chapter {c}")
+                        }
+                        (None, None, Some(t)) => {
+                            format!("{title} - {t}")
+                        }
+                        (..) => format!("{title} -- unknown chapter"),
+                    };
+
+                if c.attributes.pages == 0
+                    && c.attributes.external_url.is_some_and(|s| !s.is_empty())
+                {
+                    title += " (External)";
+                }
+
+                // This is probably unnecessary (aw-rss will consume rfc3339) but matches the
+                // old Go code exactly
+                let pub_date =
+                    DateTime::parse_from_rfc3339(&c.attributes.created_at)?.to_utc().to_rfc2822();
+
+                Ok(ItemBuilder::default()
+                    .title(Some(title))
+                    .link(Some(format!("https://mangadex.org/chapter/{}", c.id)))
+                    .guid(Some(GuidBuilder::default().value(c.id).build()))
+                    .pub_date(Some(pub_date))
+                    .build())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        chapters.extend(items);
 
 
         offset += PAGE_SIZE;
     }
 
-    Ok(chapters)
+    Ok((chapters, extras))
 }
 
 #[derive(Default, Debug, Clone, Deserialize)]
@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags, params};
+
+use crate::error::Result;
+
+/// Where in aw-rss's database a migration rewrites values.
+#[derive(Debug, Clone, Copy)]
+enum Column {
+    /// The command aw-rss runs for external feeds, like `!rss-scrapers mangadex <uuid>`.
+    FeedUrl,
+    /// The guid aw-rss uses to tell which items it has already seen.
+    ItemKey,
+}
+
+impl Column {
+    fn table(self) -> &'static str {
+        match self {
+            Self::FeedUrl => "feeds",
+            Self::ItemKey => "items",
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            Self::FeedUrl => "url",
+            Self::ItemKey => "key",
+        }
+    }
+}
+
+/// A change in how a scraper is run or how it builds guids, which aw-rss's stored feeds and items
+/// have to follow or every item would show up again as new.
+struct Migration {
+    name: &'static str,
+    column: Column,
+    /// A LIKE pattern matching every value in the old format.
+    old: &'static str,
+    /// The value in the new format, or None to leave it alone.
+    rewrite: fn(&str) -> Option<String>,
+}
+
+// In the order the formats changed. Add new ones to the end.
+const MIGRATIONS: &[Migration] = &[
+    // gelbooru-rss was merged into rss-scrapers
+    Migration {
+        name: "gelbooru-command",
+        column: Column::FeedUrl,
+        old: "!gelbooru-rss %",
+        rewrite: |url| {
+            let query = url.strip_prefix("!gelbooru-rss ")?;
+            Some(format!("!rss-scrapers gelbooru {query}"))
+        },
+    },
+    // mangadex-rss took flags before the series ID, which are all gone now
+    Migration {
+        name: "mangadex-command",
+        column: Column::FeedUrl,
+        old: "!mangadex-rss %",
+        rewrite: |url| {
+            let series = url.rsplit_once(' ')?.1;
+            Some(format!("!rss-scrapers mangadex {series}"))
+        },
+    },
+    // Chapter guids were links to the chapter, now they're the chapter ID
+    Migration {
+        name: "mangadex-guid",
+        column: Column::ItemKey,
+        old: "https://mangadex.org/chapter/%",
+        rewrite: |key| Some(key.strip_prefix("https://mangadex.org/chapter/")?.to_string()),
+    },
+];
+
+#[derive(Debug)]
+pub struct Change {
+    pub migration: &'static str,
+    pub table: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// Rewrites the feeds and items in an aw-rss database that are still in the formats older
+/// versions of the scrapers used, returning every change.
+///
+/// Everything happens in one transaction, so a failure leaves the database untouched. With
+/// `dry_run` the changes are found but rolled back.
+pub fn run(db: &Path, dry_run: bool) -> Result<Vec<Change>> {
+    // Opening a mistyped path shouldn't create an empty database
+    let mut conn =
+        Connection::open_with_flags(db, OpenFlags::default() - OpenFlags::SQLITE_OPEN_CREATE)?;
+    let tx = conn.transaction()?;
+
+    let mut changes = Vec::new();
+
+    for m in MIGRATIONS {
+        let (table, column) = (m.column.table(), m.column.column());
+
+        let rows = tx
+            .prepare(&format!("SELECT id, {column} FROM {table} WHERE {column} LIKE ?1"))?
+            .query_map([m.old], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut update = tx.prepare(&format!("UPDATE {table} SET {column} = ?1 WHERE id = ?2"))?;
+
+        for (id, old) in rows {
+            let Some(new) = (m.rewrite)(&old).filter(|new| *new != old) else {
+                continue;
+            };
+
+            update.execute(params![new, id])?;
+            changes.push(Change { migration: m.name, table, old, new });
+        }
+    }
+
+    if dry_run {
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
+
+    Ok(changes)
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+
+use crate::batch::{self, Entry, Manifest};
+use crate::command::ScraperCommand;
+use crate::error::{Error, Result};
+
+// How aw-rss runs external commands
+const COMMAND_PREFIX: &str = "!rss-scrapers ";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename = "opml")]
+struct Opml {
+    #[serde(rename = "@version")]
+    version: String,
+    #[serde(default)]
+    head: Head,
+    body: Body,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Head {
+    #[serde(default)]
+    title: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Body {
+    #[serde(default, rename = "outline")]
+    outlines: Vec<Outline>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Outline {
+    #[serde(rename = "@type", skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    #[serde(rename = "@text", default)]
+    text: String,
+    #[serde(rename = "@xmlUrl", skip_serializing_if = "Option::is_none")]
+    xml_url: Option<String>,
+    /// Readers use nested outlines for folders.
+    #[serde(default, rename = "outline", skip_serializing_if = "Vec::is_empty")]
+    children: Vec<Outline>,
+}
+
+/// Where to read the feeds to export from.
+#[derive(Debug, Clone, Copy)]
+pub enum Source<'a> {
+    /// A batch manifest
+    Manifest(&'a Path),
+    /// Every `!rss-scrapers` feed in an aw-rss database
+    AwRss(&'a Path),
+}
+
+/// Lists the feeds as OPML. Each xmlUrl is the aw-rss command for the feed, or its url under
+/// `serve_url` if the feeds are served with `rss-scrapers serve`.
+pub fn export(source: Source, serve_url: Option<&str>) -> Result<String> {
+    let commands = match source {
+        Source::Manifest(path) => batch::load(path)?.feeds.into_iter().map(|e| e.command).collect(),
+        Source::AwRss(db) => aw_rss_commands(db)?,
+    };
+
+    let outlines = commands
+        .into_iter()
+        .map(|command| {
+            let xml_url = match serve_url {
+                Some(base) => {
+                    let path: Vec<_> =
+                        command.iter().map(|arg| urlencoding::encode(arg).into_owned()).collect();
+                    format!("{}/{}", base.trim_end_matches('/'), path.join("/"))
+                }
+                None => format!("{COMMAND_PREFIX}{}", command.join(" ")),
+            };
+
+            Outline {
+                kind: Some("rss".to_string()),
+                text: command.join(" "),
+                xml_url: Some(xml_url),
+                children: Vec::new(),
+            }
+        })
+        .collect();
+
+    let opml = Opml {
+        version: "2.0".to_string(),
+        head: Head { title: "rss-scrapers".to_string() },
+        body: Body { outlines },
+    };
+
+    let mut xml = r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string();
+    xml.push('\n');
+    let mut serializer = quick_xml::se::Serializer::new(&mut xml);
+    serializer.indent(' ', 2);
+    opml.serialize(serializer)
+        .map_err(|e| Error::parse(format!("can't write opml: {e}")))?;
+    xml.push('\n');
+    Ok(xml)
+}
+
+fn aw_rss_commands(db: &Path) -> Result<Vec<Vec<String>>> {
+    let conn = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let urls = conn
+        .prepare("SELECT url FROM feeds WHERE url LIKE ?1 ORDER BY id")?
+        .query_map([format!("{COMMAND_PREFIX}%")], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(urls
+        .iter()
+        .filter_map(|url| url.strip_prefix(COMMAND_PREFIX))
+        .map(|args| args.split_whitespace().map(str::to_string).collect())
+        .collect())
+}
+
+/// Builds a batch manifest from the scraper feeds in OPML, writing each feed to `output_dir`.
+///
+/// Feeds are recognized by `!rss-scrapers` commands, or by urls under `serve_url`. Other feeds
+/// are skipped, but a scraper feed with arguments the subcommand doesn't accept is an error.
+pub fn import(opml: &str, output_dir: &Path, serve_url: Option<&str>) -> Result<String> {
+    let opml: Opml =
+        quick_xml::de::from_str(opml).map_err(|e| Error::parse(format!("invalid opml: {e}")))?;
+
+    let mut feeds: Vec<Entry> = Vec::new();
+
+    for (text, command) in commands(&opml.body.outlines, serve_url) {
+        let command = command?;
+
+        ScraperCommand::parse_args(&command)
+            .map_err(|e| Error::config(format!("invalid feed {text:?}: {e}")))?;
+
+        // Readers often list the same feed in several folders
+        if feeds.iter().any(|f| f.command == command) {
+            continue;
+        }
+
+        let name: String = command
+            .join("-")
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+
+        // Different commands can end up with the same name, like gelbooru queries that only
+        // differ in punctuation
+        let mut output = output_dir.join(format!("{name}.xml"));
+        for n in 2.. {
+            if !feeds.iter().any(|f| f.output == output) {
+                break;
+            }
+            output = output_dir.join(format!("{name}-{n}.xml"));
+        }
+
+        feeds.push(Entry { output, command, format: None });
+    }
+
+    toml::to_string(&Manifest { format: None, feeds }).map_err(Error::config)
+}
+
+// Every scraper feed in the outlines and their folders, in order.
+fn commands<'a>(
+    outlines: &'a [Outline],
+    serve_url: Option<&str>,
+) -> Vec<(&'a str, Result<Vec<String>>)> {
+    outlines
+        .iter()
+        .flat_map(|outline| {
+            let command = outline.xml_url.as_deref().and_then(|url| command(url, serve_url));
+            command
+                .map(|c| (outline.text.as_str(), c))
+                .into_iter()
+                .chain(commands(&outline.children, serve_url))
+        })
+        .collect()
+}
+
+// The subcommand and its arguments, if the url is for one of the scrapers.
+fn command(url: &str, serve_url: Option<&str>) -> Option<Result<Vec<String>>> {
+    if let Some(args) = url.strip_prefix(COMMAND_PREFIX) {
+        return Some(Ok(args.split_whitespace().map(str::to_string).collect()));
+    }
+
+    let path = url.strip_prefix(serve_url?.trim_end_matches('/'))?;
+    // Like ?format=json, which is for the server rather than the scraper
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    Some(
+        path.split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| urlencoding::decode(s).map(|s| s.into_owned()))
+            .collect::<Result<_, _>>()
+            .map_err(|e| Error::parse(format!("invalid url {url}: {e}"))),
+    )
+}
@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+
+use atom_syndication::{Category, Content, Entry, FixedDateTime, Link, Person, Text};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use rss::Channel;
+use serde::{Deserialize, Serialize};
+
+use crate::scraper::{Extras, Feed};
+
+const AW_RSS_NAMESPACE: &str = "https://github.com/awused/aw-rss";
+const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Rss,
+    Atom,
+    /// JSON Feed 1.1
+    Json,
+}
+
+pub fn render(feed: Feed, format: Format) -> String {
+    match format {
+        Format::Rss => rss(feed),
+        Format::Atom => atom(feed),
+        Format::Json => json(feed),
+    }
+}
+
+fn rss(feed: Feed) -> String {
+    let mut channel = feed.channel;
+
+    if let Some(etag) = feed.etag {
+        let mut extensions = rss::extension::ExtensionMap::new();
+        let mut ext = rss::extension::Extension::default();
+        ext.set_name("aw-rss:etag".to_string());
+        ext.set_value(Some(etag));
+        let mut map = BTreeMap::new();
+        map.insert(String::new(), vec![ext]);
+        extensions.insert(String::new(), map);
+
+        channel.set_extensions(extensions);
+    }
+
+    channel.to_string()
+}
+
+fn atom(feed: Feed) -> String {
+    let channel = feed.channel;
+
+    let entries: Vec<_> = channel.items().iter().map(|item| entry(&channel, item)).collect();
+
+    // Atom requires an updated time on the feed, so use the newest item and fall back to the
+    // build time when there are no items.
+    let updated = entries
+        .iter()
+        .map(|e| e.updated)
+        .max()
+        .or_else(|| channel.last_build_date().and_then(parse_date))
+        .unwrap_or_else(|| Utc::now().fixed_offset());
+
+    let author = channel
+        .managing_editor()
+        .or_else(|| {
+            channel
+                .dublin_core_ext()
+                .and_then(|dc| dc.creators().first())
+                .map(String::as_str)
+        })
+        .unwrap_or(channel.title());
+
+    let mut atom = atom_syndication::Feed {
+        title: Text::plain(channel.title()),
+        id: channel.link().to_string(),
+        updated,
+        authors: vec![Person { name: author.to_string(), ..Person::default() }],
+        categories: channel.categories().iter().map(category).collect(),
+        links: vec![Link { href: channel.link().to_string(), ..Link::default() }],
+        subtitle: Some(channel.description()).filter(|d| !d.is_empty()).map(Text::html),
+        entries,
+        ..atom_syndication::Feed::default()
+    };
+
+    if let Some(etag) = feed.etag {
+        let ext = atom_syndication::extension::Extension {
+            name: "aw-rss:etag".to_string(),
+            value: Some(etag),
+            ..atom_syndication::extension::Extension::default()
+        };
+        let mut map = BTreeMap::new();
+        map.insert(String::new(), vec![ext]);
+        atom.extensions.insert(String::new(), map);
+        atom.namespaces.insert("aw-rss".to_string(), AW_RSS_NAMESPACE.to_string());
+    }
+
+    atom.to_string()
+}
+
+fn entry(channel: &Channel, item: &rss::Item) -> Entry {
+    let updated = item
+        .pub_date()
+        .and_then(parse_date)
+        .or_else(|| channel.last_build_date().and_then(parse_date))
+        .unwrap_or_else(|| Utc::now().fixed_offset());
+
+    let link = item
+        .link()
+        .or_else(|| item.guid().filter(|g| g.is_permalink()).map(|g| g.value()));
+
+    // Atom ids have to be IRIs, but most of the guids are bare ids from the sites.
+    let id = match item.guid().map(|g| g.value()).or(link) {
+        Some(id) if url::Url::parse(id).is_ok() => id.to_string(),
+        Some(id) => format!("urn:rss-scrapers:{}", urlencoding::encode(id)),
+        None => format!("urn:rss-scrapers:{}", urlencoding::encode(item.title().unwrap_or(""))),
+    };
+
+    let authors = item
+        .author()
+        .into_iter()
+        .chain(
+            item.dublin_core_ext()
+                .into_iter()
+                .flat_map(|dc| dc.creators())
+                .map(String::as_str),
+        )
+        .map(|name| Person { name: name.to_string(), ..Person::default() })
+        .collect();
+
+    Entry {
+        title: Text::plain(item.title().unwrap_or_default()),
+        id,
+        updated,
+        authors,
+        categories: item.categories().iter().map(category).collect(),
+        links: link
+            .map(|href| Link { href: href.to_string(), ..Link::default() })
+            .into_iter()
+            .collect(),
+        published: item.pub_date().and_then(parse_date),
+        summary: item.description().map(Text::html),
+        content: item.content().map(|c| Content {
+            value: Some(c.to_string()),
+            content_type: Some("html".to_string()),
+            ..Content::default()
+        }),
+        ..Entry::default()
+    }
+}
+
+fn json(feed: Feed) -> String {
+    let Feed { channel, etag, mut extras } = feed;
+
+    let items = channel
+        .items()
+        .iter()
+        .map(|item| {
+            let id = item
+                .guid()
+                .map(|g| g.value())
+                .or(item.link())
+                .or(item.title())
+                .unwrap_or_default();
+
+            // Items need some content, so fall back to an empty string when there is none.
+            let (content_html, content_text) = match item.content().or(item.description()) {
+                Some(c) => (Some(c), None),
+                None => (None, Some("")),
+            };
+
+            JsonItem {
+                id,
+                url: item.link(),
+                title: item.title(),
+                content_html,
+                content_text,
+                summary: item.content().and(item.description()),
+                date_published: item.pub_date().and_then(parse_date).map(|d| d.to_rfc3339()),
+                authors: item
+                    .author()
+                    .into_iter()
+                    .chain(
+                        item.dublin_core_ext()
+                            .into_iter()
+                            .flat_map(|dc| dc.creators())
+                            .map(String::as_str),
+                    )
+                    .map(|name| JsonAuthor { name })
+                    .collect(),
+                tags: item.categories().iter().map(rss::Category::name).collect(),
+                rss_scrapers: extras.remove(id),
+            }
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: JSON_FEED_VERSION,
+        title: channel.title(),
+        home_page_url: Some(channel.link()).filter(|l| !l.is_empty()),
+        description: Some(channel.description()).filter(|d| !d.is_empty()),
+        items,
+        rss_scrapers: etag.map(|etag| JsonFeedExtras { etag }),
+    };
+
+    serde_json::to_string_pretty(&feed).unwrap()
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    home_page_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    items: Vec<JsonItem<'a>>,
+    #[serde(rename = "_rss_scrapers", skip_serializing_if = "Option::is_none")]
+    rss_scrapers: Option<JsonFeedExtras>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFeedExtras {
+    etag: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonItem<'a> {
+    id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_text: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonAuthor<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<&'a str>,
+    #[serde(rename = "_rss_scrapers", skip_serializing_if = "Option::is_none")]
+    rss_scrapers: Option<Extras>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonAuthor<'a> {
+    name: &'a str,
+}
+
+fn category(c: &rss::Category) -> Category {
+    Category {
+        term: c.name().to_string(),
+        scheme: c.domain().map(str::to_string),
+        ..Category::default()
+    }
+}
+
+fn parse_date(date: &str) -> Option<FixedDateTime> {
+    DateTime::parse_from_rfc2822(date).ok()
+}
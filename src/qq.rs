@@ -1,135 +1,193 @@
-use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufReader, Cursor, ErrorKind};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::thread;
 use std::time::Duration;
 
-use color_eyre::Result;
-use color_eyre::eyre::OptionExt;
-use reqwest::StatusCode;
+use clap::Args;
 use reqwest::blocking::multipart::Form;
-use reqwest::header::{ETAG, IF_NONE_MATCH};
 use reqwest_cookie_store::{CookieStore, CookieStoreMutex};
 use rss::Channel;
-use rss::extension::{Extension, ExtensionMap};
 use scraper::{Html, Selector};
 use serde::Deserialize;
+use serde_with::serde_as;
 use tracing::error_span;
 
+use crate::config::{self, ExpandPath};
+use crate::error::{Error, OptionExt, Result};
+use crate::http::{self, Client, Modified};
+use crate::scraper::{Feed, Fetched, Scraper};
 
+const HOST: &str = "https://forum.questionablequesting.com";
+
+#[serde_as]
 #[derive(Debug, Deserialize)]
 struct Config {
     username: String,
     password: String,
+    #[serde_as(as = "ExpandPath")]
     cookie_jar: PathBuf,
 }
 
-pub fn get(thread_id: String, last_etag: Option<String>) -> Result<()> {
-    let config: Config = awconf::load_config("qq-rss", None::<&str>, None::<&str>)?.0;
+#[derive(Debug, Clone, Args)]
+pub struct QQ {
+    /// Thread ID
+    /// /threads/ab-cd.1234 has an ID of ab-cd.1234
+    #[arg(allow_hyphen_values = true)]
+    pub thread_id: String,
+}
+
+impl Scraper for QQ {
+    fn name(&self) -> &'static str {
+        "qq"
+    }
+
+    fn feed(&self) -> String {
+        self.thread_id.clone()
+    }
+
+    fn fetch(&self, etag: Option<&str>) -> Result<Fetched> {
+        get(&self.thread_id, etag)
+    }
+}
+
+struct Session {
+    config: Config,
+    cookie_store: Arc<CookieStoreMutex>,
+    client: Client,
+}
+
+// Shared by every QQ feed in the process so batch runs only log in once.
+fn session() -> Result<&'static Session> {
+    static SESSION: OnceLock<Session> = OnceLock::new();
+
+    if let Some(session) = SESSION.get() {
+        return Ok(session);
+    }
+
+    let config: Config = config::section("qq", "qq-rss", None)?;
 
     let cookie_store = match File::open(&config.cookie_jar) {
-        Ok(f) => CookieStore::load(BufReader::new(f), |c| serde_json::from_str(c)).unwrap(),
+        Ok(f) => {
+            CookieStore::load(BufReader::new(f), |c| serde_json::from_str(c)).map_err(|e| {
+                Error::config(format!("invalid cookie jar {}: {e}", config.cookie_jar.display()))
+            })?
+        }
         Err(e) if e.kind() == ErrorKind::NotFound => CookieStore::new(),
         Err(e) => return Err(e.into()),
     };
 
     let cookie_store = Arc::new(CookieStoreMutex::new(cookie_store));
 
-    let client = reqwest::blocking::Client::builder()
-        .cookie_provider(cookie_store.clone())
-        .build()?;
+    let client = http::builder("qq", None)?.cookie_provider(cookie_store.clone()).build()?;
 
-    let url = format!(
-        "https://forum.questionablequesting.com/threads/{thread_id}/threadmarks.rss?category_id=1"
-    );
-    let mut req = client.get(&url);
-    // Only bother setting etag the first time, just in case weird things happen
-    if let Some(etag) = last_etag {
-        req = req.header(IF_NONE_MATCH, etag);
-    }
-    let resp = req.send()?;
+    Ok(SESSION.get_or_init(|| Session { config, cookie_store, client }))
+}
 
-    if resp.status() == StatusCode::NOT_MODIFIED {
-        println!("not modified");
-        return Ok(());
-    }
+fn get(thread_id: &str, last_etag: Option<&str>) -> Result<Fetched> {
+    let session = session()?;
 
-    let mut etag = resp
-        .headers()
-        .get(ETAG)
-        .and_then(|etag| etag.to_str().ok())
-        .map(ToString::to_string);
+    let base = http::base_url("qq", HOST);
+    let url = format!("{base}/threads/{thread_id}/threadmarks.rss?category_id=1");
+    // Only bother setting etag the first time, just in case weird things happen
+    let Some(Modified { response: resp, mut etag }) =
+        http::conditional(session.client.get(&url).any_status(), last_etag)?
+    else {
+        return Ok(Fetched::NotModified);
+    };
 
     let resp = resp.bytes()?;
-    let _span = error_span!("fetch", response = %String::from_utf8_lossy(&resp)).entered();
+    let span = error_span!("fetch", response = %String::from_utf8_lossy(&resp)).entered();
 
     let text = String::from_utf8(resp.into())?;
     let feed = Channel::read_from(Cursor::new(&text));
 
+    // Keep the whole page out of the logs for the login requests
+    drop(span);
+
     let mut feed = match feed {
         Ok(feed) => feed,
         Err(_e) => {
-            let text = client.get("https://forum.questionablequesting.com").send()?.text()?;
-            let doc = Html::parse_document(&text);
-
-            // We don't need a specific xsrf token for the login page, any valid token will do.
-            let xf_token = doc
-                .select(&Selector::parse("input[name=\"_xfToken\"]").unwrap())
-                .next()
-                .ok_or_eyre("No xfToken in initial response")?
-                .attr("value")
-                .ok_or_eyre("xfToken had no value")?
-                .to_string();
-
-            thread::sleep(Duration::from_secs(1));
-
-            let form = Form::new()
-                .text("login", config.username)
-                .text("password", config.password)
-                .text("remember", "1")
-                .text("_xfToken", xf_token);
-
-            client
-                .post("https://forum.questionablequesting.com/login/login")
-                .multipart(form)
-                .send()?
-                .text()?;
-
-            thread::sleep(Duration::from_secs(1));
-
-            let resp = client.get(&url).send()?;
-            etag = resp
-                .headers()
-                .get(ETAG)
-                .and_then(|etag| etag.to_str().ok())
-                .map(ToString::to_string);
-            Channel::read_from(Cursor::new(resp.text()?))?
+            let page = http::send(session.client.get(&base))?.text()?;
+            log_in(session, &base, &page)?;
+
+            let resp = http::send(session.client.get(&url).any_status())?;
+            etag = http::etag_of(&resp);
+            // Still getting the login page instead of the feed
+            Channel::read_from(Cursor::new(resp.text()?))
+                .map_err(|_| Error::auth("still not logged in after logging in"))?
         }
     };
 
-    {
-        let mut f = File::create(&config.cookie_jar)?;
-        let store = cookie_store.lock().unwrap();
-        store.save(&mut f, serde_json::to_string).unwrap();
-    }
+    save_cookies(session)?;
 
     // Fix the link to the thread
-    feed.set_link(format!("https://forum.questionablequesting.com/threads/{thread_id}"));
-    if let Some(etag) = etag {
-        let mut extensions = ExtensionMap::new();
-        let mut ext = Extension::default();
-        ext.set_name("aw-rss:etag".to_string());
-        ext.set_value(Some(etag));
-        let mut map = BTreeMap::new();
-        map.insert(String::new(), vec![ext]);
-        extensions.insert(String::new(), map);
-
-        feed.set_extensions(extensions);
-    }
+    feed.set_link(format!("{HOST}/threads/{thread_id}"));
+
+    Ok(Feed { channel: feed, etag, ..Feed::default() }.into())
+}
+
+// Logs in using the xsrf token from any page on the forum.
+fn log_in(session: &Session, base: &str, page: &str) -> Result<()> {
+    let doc = Html::parse_document(page);
 
-    println!("{feed}");
+    // We don't need a specific xsrf token for the login page, any valid token will do.
+    let xf_token = doc
+        .select(&Selector::parse("input[name=\"_xfToken\"]").unwrap())
+        .next()
+        .ok_or_parse("No xfToken in initial response")?
+        .attr("value")
+        .ok_or_parse("xfToken had no value")?
+        .to_string();
 
+    thread::sleep(Duration::from_secs(1));
+
+    let form = Form::new()
+        .text("login", session.config.username.clone())
+        .text("password", session.config.password.clone())
+        .text("remember", "1")
+        .text("_xfToken", xf_token);
+
+    http::send(session.client.post(format!("{base}/login/login")).multipart(form).any_status())?
+        .text()?;
+
+    thread::sleep(Duration::from_secs(1));
     Ok(())
 }
+
+fn save_cookies(session: &Session) -> Result<()> {
+    let mut f = File::create(&session.config.cookie_jar)?;
+    let store = session.cookie_store.lock().unwrap();
+    store
+        .save(&mut f, serde_json::to_string)
+        .map_err(|e| Error::config(format!("can't save cookie jar: {e}")))
+}
+
+/// Logs in unless the saved cookies already are, then saves them, without fetching any thread.
+pub(crate) fn check_login() -> Result<()> {
+    let session = session()?;
+    let base = http::base_url("qq", HOST);
+
+    let page = http::send(session.client.get(&base))?.text()?;
+    if logged_in(&page) {
+        return save_cookies(session);
+    }
+
+    log_in(session, &base, &page)?;
+
+    if !logged_in(&http::send(session.client.get(&base))?.text()?) {
+        return Err(Error::auth("still not logged in after logging in"));
+    }
+    save_cookies(session)
+}
+
+/// The path to the cookie jar, from the QQ config.
+pub(crate) fn cookie_jar() -> Result<PathBuf> {
+    Ok(config::section::<Config>("qq", "qq-rss", None)?.cookie_jar)
+}
+
+// XenForo marks every page with whether the viewer is logged in
+fn logged_in(page: &str) -> bool {
+    Html::parse_document(page).root_element().attr("data-logged-in") == Some("true")
+}
@@ -0,0 +1,224 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use reqwest::blocking::{Client, Request, Response};
+use reqwest::header::{CONTENT_TYPE, HeaderMap};
+use reqwest::{ResponseBuilderExt, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+enum Mode {
+    Record { dir: PathBuf, next: AtomicUsize },
+    Replay { dir: PathBuf, remaining: Mutex<HashMap<(String, String), VecDeque<usize>>> },
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+static DUMP: OnceLock<(PathBuf, AtomicUsize)> = OnceLock::new();
+
+/// One request and its response, stored as `<n>.json` with the response body in `<n>.body`.
+///
+/// Request bodies aren't saved since QQ's login form holds the password.
+#[derive(Debug, Serialize, Deserialize)]
+struct Exchange {
+    method: String,
+    url: String,
+    request_headers: Vec<(String, String)>,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+/// Saves every request this process makes, and its response, to `dir`.
+///
+/// Recordings include cookies and API keys, so check them before sharing.
+pub fn record(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+
+    // Continue the numbering so several runs can be recorded into the same directory.
+    let next = exchanges(dir)?.last().map_or(0, |n| n + 1);
+    set(Mode::Record { dir: dir.to_path_buf(), next: AtomicUsize::new(next) })
+}
+
+/// Answers every request this process makes from the responses recorded in `dir` instead of
+/// the network. Repeated requests for the same url get the recorded responses in order.
+pub fn replay(dir: &Path) -> Result<()> {
+    let mut remaining: HashMap<_, VecDeque<_>> = HashMap::new();
+
+    for n in exchanges(dir)? {
+        let exchange = read(dir, n)?;
+        remaining.entry((exchange.method, exchange.url)).or_default().push_back(n);
+    }
+
+    set(Mode::Replay { dir: dir.to_path_buf(), remaining: Mutex::new(remaining) })
+}
+
+/// Writes the body of every response this process gets to its own file in `dir`, named after the
+/// url, for debugging a scraper without mixing anything into its output.
+pub fn dump(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    DUMP.set((dir.to_path_buf(), AtomicUsize::new(0)))
+        .map_err(|_| Error::config("responses are already being dumped"))
+}
+
+/// Whether requests are being recorded or replayed.
+pub(crate) fn is_active() -> bool {
+    MODE.get().is_some()
+}
+
+/// Whether requests are answered from a recording instead of the network.
+pub(crate) fn is_replaying() -> bool {
+    matches!(MODE.get(), Some(Mode::Replay { .. }))
+}
+
+fn set(mode: Mode) -> Result<()> {
+    MODE.set(mode)
+        .map_err(|_| Error::config("only one of --record and --replay can be used"))
+}
+
+// The numbers of the recorded exchanges, in the order they were made.
+fn exchanges(dir: &Path) -> Result<Vec<usize>> {
+    let mut numbers = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        if let Some(n) = name.to_str().and_then(|n| n.strip_suffix(".json")?.parse().ok()) {
+            numbers.push(n);
+        }
+    }
+
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+fn read(dir: &Path, n: usize) -> Result<Exchange> {
+    Ok(serde_json::from_slice(&fs::read(dir.join(format!("{n}.json")))?)?)
+}
+
+/// Sends the request, or answers it from the recording when replaying.
+///
+/// Failures to read or write the recording are the outer error so they're never retried.
+pub(crate) fn execute(client: &Client, request: Request) -> Result<reqwest::Result<Response>> {
+    let response = match exchange(client, request)? {
+        Ok(r) => r,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let Some((dir, next)) = DUMP.get() else {
+        return Ok(Ok(response));
+    };
+
+    let url = response.url().clone();
+    let status = response.status().as_u16();
+    let headers = pairs(response.headers());
+    let body = match response.bytes() {
+        Ok(b) => b.to_vec(),
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let name: String = format!("{}{}", url.host_str().unwrap_or_default(), url.path())
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .take(100)
+        .collect();
+    let n = next.fetch_add(1, Ordering::Relaxed);
+    fs::write(
+        dir.join(format!("{n:03}-{}.{}", name.trim_end_matches('_'), extension(&headers))),
+        &body,
+    )?;
+
+    Ok(Ok(rebuild(url, status, &headers, body)?))
+}
+
+fn extension(headers: &[(String, String)]) -> &'static str {
+    let content_type = headers
+        .iter()
+        .find(|(k, _)| k == CONTENT_TYPE.as_str())
+        .map_or("", |(_, v)| v.as_str());
+
+    if content_type.contains("json") {
+        "json"
+    } else if content_type.contains("html") {
+        "html"
+    } else if content_type.contains("xml") {
+        "xml"
+    } else {
+        "txt"
+    }
+}
+
+fn exchange(client: &Client, request: Request) -> Result<reqwest::Result<Response>> {
+    match MODE.get() {
+        None => Ok(client.execute(request)),
+        Some(Mode::Record { dir, next }) => {
+            let method = request.method().to_string();
+            let request_url = request.url().to_string();
+            let request_headers = pairs(request.headers());
+
+            let response = match client.execute(request) {
+                Ok(r) => r,
+                Err(e) => return Ok(Err(e)),
+            };
+
+            let url = response.url().clone();
+            let status = response.status().as_u16();
+            let headers = pairs(response.headers());
+            let body = match response.bytes() {
+                Ok(b) => b.to_vec(),
+                Err(e) => return Ok(Err(e)),
+            };
+
+            let exchange = Exchange { method, url: request_url, request_headers, status, headers };
+
+            let n = next.fetch_add(1, Ordering::Relaxed);
+            fs::write(dir.join(format!("{n}.body")), &body)?;
+            fs::write(dir.join(format!("{n}.json")), serde_json::to_vec_pretty(&exchange)?)?;
+
+            Ok(Ok(rebuild(url, status, &exchange.headers, body)?))
+        }
+        Some(Mode::Replay { dir, remaining }) => {
+            let key = (request.method().to_string(), request.url().to_string());
+            let Some(n) = remaining.lock().unwrap().get_mut(&key).and_then(VecDeque::pop_front)
+            else {
+                return Err(io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("no recorded response for {} {}", key.0, key.1),
+                )
+                .into());
+            };
+
+            let exchange = read(dir, n)?;
+            let body = fs::read(dir.join(format!("{n}.body")))?;
+
+            Ok(Ok(rebuild(request.url().clone(), exchange.status, &exchange.headers, body)?))
+        }
+    }
+}
+
+pub(crate) fn pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into_owned()))
+        .collect()
+}
+
+// Responses have to be read to be saved, so a new one is built from what was read.
+pub(crate) fn rebuild(
+    url: Url,
+    status: u16,
+    headers: &[(String, String)],
+    body: Vec<u8>,
+) -> Result<Response> {
+    let mut response = ::http::Response::builder().status(status).url(url);
+    for (k, v) in headers {
+        response = response.header(k, v);
+    }
+
+    let response = response
+        .body(body)
+        .map_err(|e| Error::parse(format!("invalid recorded response: {e}")))?;
+    Ok(response.into())
+}
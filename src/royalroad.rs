@@ -1,66 +1,78 @@
-use chrono::Utc;
-use color_eyre::Result;
 use regex::Regex;
-use reqwest::blocking::Client;
-use rss::{ChannelBuilder, GuidBuilder, Item, ItemBuilder};
+use rss::{Channel, ChannelBuilder, GuidBuilder, ItemBuilder};
 use scraper::{Html, Selector};
 use tracing::error_span;
 
-pub fn get() -> Result<()> {
-    let client = reqwest::blocking::Client::new();
+use crate::error::{OptionExt, Result};
+use crate::http;
+use crate::scraper::{Fetched, Scraper};
 
-    let mut items =
-        get_fictions(&client, "https://www.royalroad.com/fictions/trending".to_string())?;
+#[derive(Debug, Clone, Copy)]
+pub struct RoyalRoad;
 
-    items.extend(get_fictions(
-        &client,
-        "https://www.royalroad.com/fictions/rising-stars".to_string(),
-    )?);
+impl Scraper for RoyalRoad {
+    fn name(&self) -> &'static str {
+        "royal-road"
+    }
 
-    for page in 1..=5 {
-        items.extend(get_fictions(
-            &client,
-            format!("https://www.royalroad.com/fictions/weekly-popular?page={page}"),
-        )?);
+    // The feed is assembled from several listing pages, so there's no single validator to send.
+    fn fetch(&self, _etag: Option<&str>) -> Result<Fetched> {
+        get()
     }
+}
 
-    let feed = ChannelBuilder::default()
-        .title("Royal Road - Trending/Popular".to_string())
-        .link("https://www.royalroad.com/fictions/trending".to_string())
-        .ttl(Some((60 * 12).to_string()))
-        .items(items)
-        .build();
+fn get() -> Result<Fetched> {
+    let client = http::client("royal-road")?;
+
+    let base = http::base_url("royal-road", "https://www.royalroad.com");
 
-    print!("{feed}");
-    Ok(())
+    let mut urls =
+        vec![format!("{base}/fictions/trending"), format!("{base}/fictions/rising-stars")];
+    urls.extend((1..=5).map(|page| format!("{base}/fictions/weekly-popular?page={page}")));
+
+    let pages = urls
+        .into_iter()
+        .map(|url| {
+            let page = http::send(client.get(url))?.bytes()?;
+            Ok(String::from_utf8(page.into())?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(parse(&pages)?.into())
 }
 
-fn get_fictions(client: &Client, url: String) -> Result<Vec<Item>> {
+/// Builds the feed from the trending, rising stars and weekly popular listings, in that order.
+pub fn parse(pages: &[String]) -> Result<Channel> {
     let selector = Selector::parse("h2.fiction-title > a").unwrap();
     let re = Regex::new(r#"^/fiction/(\d+)(/|$)"#).unwrap();
 
-    let page = client.get(url).send()?.bytes()?;
-    let _span = error_span!("response", page = %String::from_utf8_lossy(&page)).entered();
+    let mut items = Vec::new();
 
-    let page = String::from_utf8(page.into())?;
-    let doc = Html::parse_document(&page);
+    for page in pages {
+        let _span = error_span!("response", page = %page).entered();
 
-    let now = Utc::now().to_rfc2822();
+        let doc = Html::parse_document(page);
 
-    Ok(doc
-        .select(&selector)
-        .map(|a| {
+        for a in doc.select(&selector) {
             let title: String = a.text().collect();
-            let href = a.attr("href").unwrap();
+            let href = a.attr("href").ok_or_parse("Fiction has no link")?;
 
-            let cap = re.captures(href).unwrap();
+            let cap = re.captures(href).ok_or_parse("Fiction link has no id")?;
 
-            ItemBuilder::default()
-                .title(Some(title))
-                .link(Some(format!("https://www.royalroad.com{href}")))
-                .guid(Some(GuidBuilder::default().value(cap[1].to_string()).build()))
-                .pub_date(Some(now.clone()))
-                .build()
-        })
-        .collect())
+            items.push(
+                ItemBuilder::default()
+                    .title(Some(title))
+                    .link(Some(format!("https://www.royalroad.com{href}")))
+                    .guid(Some(GuidBuilder::default().value(cap[1].to_string()).build()))
+                    .build(),
+            );
+        }
+    }
+
+    Ok(ChannelBuilder::default()
+        .title("Royal Road - Trending/Popular".to_string())
+        .link("https://www.royalroad.com/fictions/trending".to_string())
+        .ttl(Some((60 * 12).to_string()))
+        .items(items)
+        .build())
 }
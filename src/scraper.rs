@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use rss::Channel;
+
+use crate::error::Result;
+
+/// A single feed source. Implementations fetch and build the feed but never print it, leaving
+/// rendering to the caller.
+pub trait Scraper {
+    /// The name of the subcommand for this scraper.
+    fn name(&self) -> &'static str;
+
+    /// Distinguishes feeds from the same scraper. Empty for scrapers with only one feed.
+    fn feed(&self) -> String {
+        String::new()
+    }
+
+    /// Fetches the feed. If the site supports conditional requests and nothing changed since the
+    /// run that returned `etag`, this returns [`Fetched::NotModified`].
+    ///
+    /// This is the feed straight from the site, use [`scrape`] for the filtered feed.
+    fn fetch(&self, etag: Option<&str>) -> Result<Fetched>;
+}
+
+#[derive(Debug)]
+pub enum Fetched {
+    /// The site reported that nothing changed since the last etag.
+    NotModified,
+    Feed(Box<Feed>),
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Feed {
+    pub channel: Channel,
+    /// Passed back to the scraper through --etag on the next run.
+    pub etag: Option<String>,
+    /// Scraper-specific data for items, keyed by guid, that doesn't fit in rss.
+    pub extras: HashMap<String, Extras>,
+}
+
+pub type Extras = serde_json::Map<String, serde_json::Value>;
+
+impl From<Feed> for Fetched {
+    fn from(feed: Feed) -> Self {
+        Self::Feed(Box::new(feed))
+    }
+}
+
+impl From<Channel> for Fetched {
+    fn from(channel: Channel) -> Self {
+        Feed { channel, ..Feed::default() }.into()
+    }
+}
+
+/// Fetches a feed, drops any items the configured filters reject, and fills in anything the
+/// scraper itself can't, like publication dates for sites that don't list any.
+pub fn scrape(scraper: &dyn Scraper, etag: Option<&str>) -> Result<Fetched> {
+    let filters = &crate::config::get()?.filters;
+    let mut fetched = scraper.fetch(etag)?;
+
+    if let Fetched::Feed(feed) = &mut fetched {
+        crate::filter::apply(filters, scraper, feed);
+        crate::first_seen::stamp(scraper, &mut feed.channel)?;
+    }
+
+    Ok(fetched)
+}
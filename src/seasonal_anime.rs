@@ -2,15 +2,19 @@ use std::collections::{HashMap, HashSet};
 use std::io::{BufReader, Cursor};
 
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
-use color_eyre::Result;
 use regex::Regex;
 use reqwest::Url;
 use rss::{Channel, ChannelBuilder};
 use serde::Deserialize;
 use tracing::error_span;
 
+use crate::config;
+use crate::error::Result;
+use crate::http::{self, Modified};
+use crate::scraper::{Feed, Fetched, Scraper};
+
 #[derive(Debug, Deserialize)]
-struct Config {
+pub(crate) struct Config {
     title: String,
     ongoing: Vec<String>,
 
@@ -18,9 +22,26 @@ struct Config {
     quarters: HashMap<String, Vec<String>>,
 }
 
-pub fn get() -> Result<()> {
-    let client = reqwest::blocking::Client::new();
-    let conf: Config = awconf::load_config("seasonal-anime-rss", None::<&str>, None::<&str>)?.0;
+#[derive(Debug, Clone, Copy)]
+pub struct SeasonalAnime;
+
+impl Scraper for SeasonalAnime {
+    fn name(&self) -> &'static str {
+        "seasonal-anime"
+    }
+
+    fn fetch(&self, etag: Option<&str>) -> Result<Fetched> {
+        get(etag)
+    }
+}
+
+pub(crate) fn config() -> Result<Config> {
+    config::section("seasonal-anime", "seasonal-anime-rss", None)
+}
+
+fn get(etag: Option<&str>) -> Result<Fetched> {
+    let client = http::client("seasonal-anime")?;
+    let conf = config()?;
 
     let quarter_re = Regex::new(r#"^(\d{4})[Qq]([1-4])$"#).unwrap();
 
@@ -58,7 +79,8 @@ pub fn get() -> Result<()> {
         + &searches.iter().map(String::as_str).collect::<Vec<_>>().join(")|(")
         + ")";
 
-    let mut rss_url = Url::parse("https://nyaa.si/?page=rss").unwrap();
+    let mut rss_url =
+        Url::parse(&format!("{}/?page=rss", http::base_url("seasonal-anime", "https://nyaa.si")))?;
     let mut search_url = Url::parse("https://nyaa.si/").unwrap();
 
     rss_url.query_pairs_mut().append_pair("q", &search);
@@ -68,13 +90,16 @@ pub fn get() -> Result<()> {
     feed.title(&conf.title).link(search_url.to_string()).ttl(Some("60".into()));
 
     if searches.is_empty() {
-        print!("{}", feed.build());
-        return Ok(());
+        return Ok(feed.build().into());
     }
 
     let _span = error_span!("nyaa_url", rss_url = %rss_url, search_url = %search_url).entered();
 
-    let base_feed = client.get(rss_url).send()?.bytes()?;
+    // The search is part of the url, so changes to the config won't be hidden by a stale etag.
+    let Some(Modified { response, etag }) = http::conditional(client.get(rss_url), etag)? else {
+        return Ok(Fetched::NotModified);
+    };
+    let base_feed = response.bytes()?;
 
     let _span = error_span!("nyaa_feed", feed = %String::from_utf8_lossy(&base_feed)).entered();
 
@@ -82,6 +107,5 @@ pub fn get() -> Result<()> {
     let base_feed = Channel::read_from(BufReader::new(Cursor::new(base_feed)))?;
     feed.items(base_feed.items);
 
-    print!("{}", feed.build());
-    Ok(())
+    Ok(Feed { channel: feed.build(), etag, ..Feed::default() }.into())
 }
@@ -0,0 +1,184 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Cursor;
+use std::iter;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use tiny_http::{Header, Request, Response, Server};
+use tracing::{error, warn};
+use tracing_error::ExtractSpanTrace;
+
+use crate::command::ScraperCommand;
+use crate::error::Result;
+use crate::output::{self, Format};
+use crate::scraper::{Feed, Fetched, scrape};
+
+// Used when a feed doesn't specify its own ttl
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+// How long a failed scrape is answered from memory, so a broken feed isn't retried on every request
+const FAILURE_TTL: Duration = Duration::from_secs(5 * 60);
+
+struct Entry {
+    expires: Instant,
+    /// The feed, or why it couldn't be scraped.
+    feed: std::result::Result<Cached, String>,
+}
+
+struct Cached {
+    feed: Feed,
+    fetched: DateTime<Utc>,
+    /// A hash of what's in the feed, the same for every fetch until something changes.
+    hash: u64,
+}
+
+/// Serves feeds over http, mapping paths to subcommands so /mangadex/<uuid> is the same as
+/// `rss-scrapers mangadex <uuid>`. Each path segment is one argument and `?format=` overrides
+/// the default format.
+///
+/// Requests are handled one at a time, so the per-site delays still apply.
+pub fn run(address: &str, format: Format) -> Result<()> {
+    let server = Server::http(address).map_err(std::io::Error::other)?;
+    let mut cache = HashMap::new();
+
+    for request in server.incoming_requests() {
+        let now = Instant::now();
+        cache.retain(|_, e: &mut Entry| e.expires > now);
+
+        let response = handle(&request, &mut cache, format);
+        // Clients that hang up before reading their feed don't stop the server
+        if let Err(e) = request.respond(response) {
+            warn!("Failed to respond: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+fn handle(
+    request: &Request,
+    cache: &mut HashMap<String, Entry>,
+    default_format: Format,
+) -> Response<Cursor<Vec<u8>>> {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+
+    let format = match query.split('&').find_map(|p| p.strip_prefix("format=")) {
+        Some(f) => match Format::from_str(f, true) {
+            Ok(f) => f,
+            Err(e) => return error(400, &e),
+        },
+        None => default_format,
+    };
+
+    if !cache.contains_key(path) {
+        let args = match path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(urlencoding::decode)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(args) => args,
+            Err(e) => return error(400, &e),
+        };
+
+        let scraper = match ScraperCommand::parse_args(args.iter().map(|a| a.to_string())) {
+            Ok(cmd) => cmd.into_scraper(),
+            Err(e) => return error(404, &e),
+        };
+
+        let feed = match scrape(&*scraper, None) {
+            Ok(Fetched::Feed(feed)) => *feed,
+            // Can't happen without an etag
+            Ok(Fetched::NotModified) => return error(500, &"Unexpected not modified response"),
+            Err(e) => {
+                let e = describe(&e);
+                error!("Failed to scrape {path}: {e}");
+                let expires = Instant::now() + FAILURE_TTL;
+                cache.insert(path.to_string(), Entry { expires, feed: Err(e.clone()) });
+                return error(502, &e);
+            }
+        };
+
+        let ttl = feed
+            .channel
+            .ttl()
+            .and_then(|ttl| ttl.parse::<u64>().ok())
+            .map_or(DEFAULT_TTL, |minutes| Duration::from_secs(minutes * 60));
+
+        let hash = hash(&feed);
+        let cached = Cached { feed, fetched: Utc::now(), hash };
+        cache.insert(path.to_string(), Entry { expires: Instant::now() + ttl, feed: Ok(cached) });
+    }
+
+    let cached = match &cache[path].feed {
+        Ok(cached) => cached,
+        Err(e) => return error(502, e),
+    };
+    let body = output::render(cached.feed.clone(), format);
+
+    let mut hasher = DefaultHasher::new();
+    (cached.hash, format).hash(&mut hasher);
+    let etag = format!("\"{:x}\"", hasher.finish());
+    let last_modified = cached.fetched.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+
+    let header = |name: &'static str| {
+        request.headers().iter().find(|h| h.field.equiv(name)).map(|h| h.value.as_str())
+    };
+
+    // If-None-Match takes precedence when both are sent
+    let not_modified = match (header("If-None-Match"), header("If-Modified-Since")) {
+        (Some(inm), _) => inm.split(',').any(|e| e.trim() == etag || e.trim() == "*"),
+        (None, Some(ims)) => DateTime::parse_from_rfc2822(ims)
+            .is_ok_and(|ims| cached.fetched.timestamp() <= ims.timestamp()),
+        (None, None) => false,
+    };
+
+    let response = if not_modified {
+        Response::from_string("").with_status_code(304)
+    } else {
+        let content_type = match format {
+            Format::Rss => "application/rss+xml; charset=utf-8",
+            Format::Atom => "application/atom+xml; charset=utf-8",
+            Format::Json => "application/feed+json; charset=utf-8",
+        };
+        Response::from_string(body).with_header(header_value("Content-Type", content_type))
+    };
+
+    response
+        .with_header(header_value("ETag", &etag))
+        .with_header(header_value("Last-Modified", &last_modified))
+}
+
+// Atom and JSON Feed fall back to the current time for items without dates, so hashing the
+// rendered body would give a new etag on every request. RSS and the extras never do.
+fn hash(feed: &Feed) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    feed.channel.to_string().hash(&mut hasher);
+
+    let extras: BTreeMap<_, _> = feed.extras.iter().collect();
+    serde_json::to_string(&extras).unwrap_or_default().hash(&mut hasher);
+
+    hasher.finish()
+}
+
+fn error(status: u16, e: &dyn std::fmt::Display) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(e.to_string())
+        .with_status_code(status)
+        .with_header(header_value("Content-Type", "text/plain; charset=utf-8"))
+}
+
+// Every cause, without the span trace's copy of the message since it would only repeat the
+// error's, and the responses it records are too big for an error page.
+fn describe(e: &(dyn std::error::Error + 'static)) -> String {
+    iter::successors(Some(e), |e| e.source())
+        .filter(|e| e.span_trace().is_none())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn header_value(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
@@ -1,61 +1,89 @@
-use chrono::Utc;
-use color_eyre::Result;
-use color_eyre::eyre::OptionExt;
-use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
+use clap::Args;
+use rss::{Channel, ChannelBuilder, GuidBuilder, ItemBuilder};
 use scraper::{Html, Selector};
 use tracing::error_span;
 
-pub fn get(game: String) -> Result<()> {
-    let client = reqwest::blocking::Client::new();
+use crate::error::{OptionExt, Result};
+use crate::http::{self, Modified};
+use crate::scraper::{Feed, Fetched, Scraper};
 
-    let url = format!("https://tfgames.site/?module=viewgame&id={game}");
-    let a_select = Selector::parse(".download-link > a").unwrap();
-    let version_select = Selector::parse(".download-card-header").unwrap();
+#[derive(Debug, Clone, Args)]
+pub struct Tfgames {
+    /// Game ID
+    /// https://tfgames.site/?module=viewgame&id=1234 has an ID of 1234
+    #[arg(allow_hyphen_values = true)]
+    pub game_id: String,
+}
+
+impl Scraper for Tfgames {
+    fn name(&self) -> &'static str {
+        "tfgames"
+    }
+
+    fn feed(&self) -> String {
+        self.game_id.clone()
+    }
+
+    fn fetch(&self, etag: Option<&str>) -> Result<Fetched> {
+        get(&self.game_id, etag)
+    }
+}
+
+fn get(game: &str, etag: Option<&str>) -> Result<Fetched> {
+    let client = http::client("tfgames")?;
+
+    let base = http::base_url("tfgames", "https://tfgames.site");
 
-    let page = client.get(url.clone()).send()?.bytes()?;
+    let Some(Modified { response, etag }) =
+        http::conditional(client.get(format!("{base}/?module=viewgame&id={game}")), etag)?
+    else {
+        return Ok(Fetched::NotModified);
+    };
+    let page = response.bytes()?;
     let _span = error_span!("response", page = %String::from_utf8_lossy(&page)).entered();
 
     let page = String::from_utf8(page.into())?;
 
+    Ok(Feed { channel: parse(game, &page)?, etag, ..Feed::default() }.into())
+}
 
-    let doc = Html::parse_document(&page);
+/// Builds the feed from the game's page.
+pub fn parse(game: &str, page: &str) -> Result<Channel> {
+    let a_select = Selector::parse(".download-link > a").unwrap();
+    let version_select = Selector::parse(".download-card-header").unwrap();
+
+    let doc = Html::parse_document(page);
     let title = doc
         .select(&Selector::parse("title").unwrap())
         .next()
-        .ok_or_eyre("No title")?
+        .ok_or_parse("No title")?
         .text()
         .collect::<String>();
 
-    let now = Utc::now().to_rfc2822();
-
     let mut items = Vec::new();
 
     for e in doc.select(&Selector::parse("div#downloads-list > .download-card").unwrap()) {
-        let header = e.select(&version_select).next().ok_or_eyre("Missing version string")?;
+        let header = e.select(&version_select).next().ok_or_parse("Missing version string")?;
         let version: String = header.text().collect();
         let version = version.trim();
 
         for a in e.select(&a_select) {
-            let href = a.attr("href").ok_or_eyre("Download link missing url")?;
+            let href = a.attr("href").ok_or_parse("Download link missing url")?;
 
             items.push(
                 ItemBuilder::default()
                     .title(Some(format!("{version} {}", a.text().collect::<String>())))
                     .link(Some(href.to_string()))
                     .guid(Some(GuidBuilder::default().value(href.to_string() + version).build()))
-                    .pub_date(Some(now.clone()))
                     .build(),
             );
         }
     }
 
-    let feed = ChannelBuilder::default()
+    Ok(ChannelBuilder::default()
         .title(title)
-        .link(url)
+        .link(format!("https://tfgames.site/?module=viewgame&id={game}"))
         .ttl(Some(360.to_string()))
         .items(items)
-        .build();
-
-    println!("{feed}");
-    Ok(())
+        .build())
 }
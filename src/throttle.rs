@@ -0,0 +1,137 @@
+use std::fs::{self, File};
+use std::io::{Read, Seek, Write};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::db;
+use crate::error::Result;
+
+/// How often requests can be sent to a site.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Limit {
+    /// The average time between requests.
+    pub delay: Duration,
+    /// How many requests can be sent at once after a quiet period.
+    pub burst: u32,
+}
+
+// Stored in a file per host, shared by every process.
+#[derive(Debug, Serialize, Deserialize)]
+struct Bucket {
+    tokens: f64,
+    /// Seconds since the epoch when tokens was last updated.
+    updated: f64,
+}
+
+/// Takes a token from the host's bucket, sleeping until one is available.
+///
+/// Buckets are kept in files under the state directory and locked while they're updated, so the
+/// limit holds across every process, like the many scrapers aw-rss runs back to back.
+pub(crate) fn wait(host: &str, limit: Limit) -> Result<()> {
+    let dir = db::state_dir()?.join("ratelimits");
+    fs::create_dir_all(&dir)?;
+
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dir.join(host))?;
+
+    let rate = 1.0 / limit.delay.as_secs_f64().max(f64::EPSILON);
+    let burst = f64::from(limit.burst.max(1));
+
+    loop {
+        file.lock()?;
+        let wait = take(&mut file, rate, burst);
+        file.unlock()?;
+
+        match wait? {
+            None => return Ok(()),
+            Some(wait) => {
+                debug!("Rate limited by {host}, waiting {wait:?}");
+                thread::sleep(wait);
+            }
+        }
+    }
+}
+
+// Takes a token if there is one, otherwise returns how long until there will be.
+fn take(file: &mut File, rate: f64, burst: f64) -> Result<Option<Duration>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    // A new or unreadable bucket starts full
+    let mut bucket =
+        serde_json::from_str(&contents).unwrap_or(Bucket { tokens: burst, updated: now });
+    bucket.tokens = (bucket.tokens + (now - bucket.updated).max(0.0) * rate).min(burst);
+    bucket.updated = now;
+
+    let wait = if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        None
+    } else {
+        Some(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+    };
+
+    file.rewind()?;
+    file.set_len(0)?;
+    file.write_all(&serde_json::to_vec(&bucket)?)?;
+    file.rewind()?;
+
+    Ok(wait)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Removed by the OS once it's closed
+    fn bucket(bucket: Option<Bucket>) -> File {
+        let mut file = tempfile::tempfile().unwrap();
+        if let Some(bucket) = bucket {
+            file.write_all(&serde_json::to_vec(&bucket).unwrap()).unwrap();
+            file.rewind().unwrap();
+        }
+        file
+    }
+
+    fn now() -> f64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64()
+    }
+
+    #[test]
+    fn burst() {
+        let mut file = bucket(None);
+
+        assert_eq!(take(&mut file, 1.0, 3.0).unwrap(), None);
+        assert_eq!(take(&mut file, 1.0, 3.0).unwrap(), None);
+        assert_eq!(take(&mut file, 1.0, 3.0).unwrap(), None);
+
+        let wait = take(&mut file, 1.0, 3.0).unwrap().unwrap();
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn refill() {
+        let mut file = bucket(Some(Bucket { tokens: 0.0, updated: now() - 0.5 }));
+
+        // Half a token back after half a second at one token per second
+        let wait = take(&mut file, 1.0, 3.0).unwrap().unwrap();
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn refill_stops_at_burst() {
+        let mut file = bucket(Some(Bucket { tokens: 0.0, updated: now() - 60.0 }));
+
+        assert_eq!(take(&mut file, 1.0, 2.0).unwrap(), None);
+        assert_eq!(take(&mut file, 1.0, 2.0).unwrap(), None);
+        assert!(take(&mut file, 1.0, 2.0).unwrap().is_some());
+    }
+}
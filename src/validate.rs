@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::DateTime;
+use reqwest::Url;
+use rss::Channel;
+
+/// Something in a feed that breaks RSS 2.0 or that readers are likely to choke on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The index of the item, or None for the channel itself.
+    pub item: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.item {
+            Some(i) => write!(f, "item {i}: {}", self.message),
+            None => write!(f, "channel: {}", self.message),
+        }
+    }
+}
+
+/// Checks a feed for missing or empty titles and links, dates that aren't RFC 2822, and guids
+/// used by more than one item, which readers treat as the same item.
+pub fn check(channel: &Channel) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut violation = |item, message: String| violations.push(Violation { item, message });
+
+    if channel.title().trim().is_empty() {
+        violation(None, "missing title".to_string());
+    }
+    if channel.link().trim().is_empty() {
+        violation(None, "missing link".to_string());
+    }
+    for (name, date) in
+        [("pubDate", channel.pub_date()), ("lastBuildDate", channel.last_build_date())]
+    {
+        if let Some(date) = date.filter(|d| DateTime::parse_from_rfc2822(d).is_err()) {
+            violation(None, format!("invalid {name} {date:?}"));
+        }
+    }
+    if let Some(ttl) = channel.ttl().filter(|ttl| ttl.parse::<u32>().is_err()) {
+        violation(None, format!("invalid ttl {ttl:?}"));
+    }
+
+    let mut guids: HashMap<&str, usize> = HashMap::new();
+
+    for (i, item) in channel.items().iter().enumerate() {
+        if item.title().is_none_or(|t| t.trim().is_empty()) {
+            violation(Some(i), "missing title".to_string());
+        }
+
+        // isPermaLink defaults to true, so bare ids like gelbooru's are "permalinks" too
+        let permalink =
+            item.guid().is_some_and(|g| g.is_permalink() && Url::parse(g.value()).is_ok());
+        if item.link().is_none_or(|l| l.trim().is_empty()) && !permalink {
+            violation(Some(i), "missing link".to_string());
+        }
+
+        if let Some(date) = item.pub_date().filter(|d| DateTime::parse_from_rfc2822(d).is_err()) {
+            violation(Some(i), format!("invalid pubDate {date:?}"));
+        }
+
+        if let Some(guid) = item.guid() {
+            if let Some(first) = guids.get(guid.value()) {
+                violation(Some(i), format!("guid {:?} is also used by item {first}", guid.value()));
+            } else {
+                guids.insert(guid.value(), i);
+            }
+        }
+    }
+
+    violations
+}
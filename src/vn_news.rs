@@ -1,46 +1,65 @@
-use chrono::Utc;
-use color_eyre::Result;
-use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
+use rss::{Channel, ChannelBuilder, GuidBuilder, ItemBuilder};
 use scraper::{Html, Selector};
 use tracing::error_span;
 
-const URL: &str = "https://erogegames.com/forums/forum/14-eroge-news/";
+use crate::error::{OptionExt, Result};
+use crate::http::{self, Modified};
+use crate::scraper::{Feed, Fetched, Scraper};
 
-pub fn get() -> Result<()> {
-    let client = reqwest::blocking::Client::new();
+const HOST: &str = "https://erogegames.com";
+const PATH: &str = "/forums/forum/14-eroge-news/";
 
-    let html = client.get(URL).send()?.bytes()?;
+#[derive(Debug, Clone, Copy)]
+pub struct VnNews;
+
+impl Scraper for VnNews {
+    fn name(&self) -> &'static str {
+        "vn-news"
+    }
+
+    fn fetch(&self, etag: Option<&str>) -> Result<Fetched> {
+        get(etag)
+    }
+}
+
+fn get(etag: Option<&str>) -> Result<Fetched> {
+    let client = http::client("vn-news")?;
+
+    let url = format!("{}{PATH}", http::base_url("vn-news", HOST));
+
+    let Some(Modified { response, etag }) = http::conditional(client.get(url), etag)? else {
+        return Ok(Fetched::NotModified);
+    };
+    let html = response.bytes()?;
     let _span = error_span!("response", html = %String::from_utf8_lossy(&html)).entered();
 
     let html = String::from_utf8(html.into())?;
-    let doc = Html::parse_document(&html);
 
-    // Page does not have times, but using the current time is good enough
-    let now = Utc::now().to_rfc2822();
+    Ok(Feed { channel: parse(&html)?, etag, ..Feed::default() }.into())
+}
+
+/// Builds the feed from the news forum's first page.
+pub fn parse(html: &str) -> Result<Channel> {
+    let doc = Html::parse_document(html);
 
-    let items: Vec<_> = doc
+    let items = doc
         .select(&Selector::parse("a[title*=\"Visual Novel Translation\"]").unwrap())
-        .filter(|a| !a.attr("title").unwrap().contains("H-RPG"))
+        .filter(|a| a.attr("title").is_some_and(|t| !t.contains("H-RPG")))
         .map(|a| {
-            let href = a.attr("href").unwrap();
+            let href = a.attr("href").ok_or_parse("Topic link without href")?;
 
-            ItemBuilder::default()
+            Ok(ItemBuilder::default()
                 .title(a.attr("title").map(str::to_string))
                 .link(Some(href.into()))
                 .guid(Some(GuidBuilder::default().value(href.to_string()).build()))
-                .pub_date(Some(now.clone()))
-                .build()
+                .build())
         })
-        .collect();
+        .collect::<Result<Vec<_>>>()?;
 
-
-    let feed = ChannelBuilder::default()
+    Ok(ChannelBuilder::default()
         .title("Visual Novel Translation Status".to_string())
-        .link(URL.to_string())
+        .link(format!("{HOST}{PATH}"))
         .items(items)
         .ttl(Some(360.to_string()))
-        .build();
-
-    print!("{feed}");
-    Ok(())
+        .build())
 }
@@ -0,0 +1,30 @@
+//! Helpers shared by the integration tests. Each test crate only uses some of them.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::{env, fs};
+
+pub fn dir(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join(name)
+}
+
+pub fn fixture(path: &str) -> Vec<u8> {
+    fs::read(dir("fixtures").join(path)).unwrap()
+}
+
+pub fn fixture_str(path: &str) -> String {
+    String::from_utf8(fixture(path)).unwrap()
+}
+
+/// Compares `actual` to the golden file `name`, or rewrites it with `UPDATE_GOLDEN=1`.
+pub fn golden(name: &str, actual: &str) {
+    let path = dir("golden").join(name);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap();
+    assert_eq!(actual, expected, "{} is out of date", path.display());
+}
@@ -0,0 +1,72 @@
+//! Runs `doctor` against config files in a temporary home directory.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn write_config(home: &Path, name: &str, contents: &str) {
+    let dir = home.join(".config").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(format!("{name}.toml")), contents).unwrap();
+}
+
+// The line for each check, like "PASS  qq config: parsed"
+fn doctor(home: &Path) -> Vec<String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_rss-scrapers"))
+        .arg("doctor")
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join(".config"))
+        .env("XDG_STATE_HOME", home.join(".local/state"))
+        .env("XDG_CACHE_HOME", home.join(".cache"))
+        .env("RSS_SCRAPERS_RETRIES", "0")
+        .env("RSS_SCRAPERS_QQ_URL", "http://127.0.0.1:1")
+        .output()
+        .unwrap();
+
+    String::from_utf8(output.stdout).unwrap().lines().map(str::to_string).collect()
+}
+
+fn check<'a>(lines: &'a [String], name: &str) -> &'a str {
+    lines.iter().find(|l| l[6..].starts_with(&format!("{name}:"))).unwrap()
+}
+
+#[test]
+fn sites_keep_legacy_gelbooru_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let home = dir.path();
+    let tag_db = home.join("legacy-tags");
+
+    write_config(home, "rss-scrapers", "[sites.gelbooru]\ndelay = 1\nburst = 2\n");
+    write_config(home, "gelbooru-rss", &format!("tag_db = {:?}\n", tag_db.to_str().unwrap()));
+
+    let lines = doctor(home);
+    assert_eq!(check(&lines, "gelbooru config"), "PASS  gelbooru config: parsed");
+    assert_eq!(
+        check(&lines, "gelbooru tag database"),
+        format!("PASS  gelbooru tag database: opened {}", tag_db.display())
+    );
+}
+
+#[test]
+fn sites_keep_legacy_qq_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let home = dir.path();
+    let cookie_jar = home.join("legacy-cookies.json");
+
+    write_config(home, "rss-scrapers", "[sites.qq]\nproxy = \"socks5h://127.0.0.1:1080\"\n");
+    write_config(
+        home,
+        "qq-rss",
+        &format!(
+            "username = \"user\"\npassword = \"pass\"\ncookie_jar = {:?}\n",
+            cookie_jar.to_str().unwrap()
+        ),
+    );
+
+    let lines = doctor(home);
+    assert_eq!(check(&lines, "qq config"), "PASS  qq config: parsed");
+    assert_eq!(
+        check(&lines, "qq cookie jar"),
+        format!("PASS  qq cookie jar: {} is writable", cookie_jar.display())
+    );
+}
@@ -0,0 +1,91 @@
+//! Runs the aw-rss migrations over a database holding every old format.
+
+use std::path::{Path, PathBuf};
+
+use rss_scrapers::migrate;
+use rusqlite::Connection;
+use tempfile::TempDir;
+
+// Only the columns the migrations touch
+fn create(dir: &TempDir) -> PathBuf {
+    let path = dir.path().join("aw-rss.db");
+
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        r#"
+        CREATE TABLE feeds (id INTEGER PRIMARY KEY, url TEXT NOT NULL);
+        CREATE TABLE items (id INTEGER PRIMARY KEY, key TEXT NOT NULL);
+
+        INSERT INTO feeds (url) VALUES
+            ('!gelbooru-rss some_series -blacklisted_tag'),
+            ('!mangadex-rss --no-external 975f3334-8395-4393-84a2-50fcaccbcdc0'),
+            ('!rss-scrapers vn-news'),
+            ('https://example.com/feed.xml');
+
+        INSERT INTO items (key) VALUES
+            ('https://mangadex.org/chapter/0b2c3d4e-0000-0000-0000-000000000000'),
+            ('https://example.com/item');
+        "#,
+    )
+    .unwrap();
+
+    path
+}
+
+fn contents(path: &Path) -> (Vec<String>, Vec<String>) {
+    let conn = Connection::open(path).unwrap();
+    let column = |sql| {
+        conn.prepare(sql)
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .unwrap()
+    };
+
+    (column("SELECT url FROM feeds ORDER BY id"), column("SELECT key FROM items ORDER BY id"))
+}
+
+#[test]
+fn rewrites_old_formats() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = create(&dir);
+
+    let changes = migrate::run(&db, false).unwrap();
+    let names: Vec<_> = changes.iter().map(|c| c.migration).collect();
+    assert_eq!(names, ["gelbooru-command", "mangadex-command", "mangadex-guid"]);
+
+    let (feeds, items) = contents(&db);
+    assert_eq!(
+        feeds,
+        [
+            "!rss-scrapers gelbooru some_series -blacklisted_tag",
+            "!rss-scrapers mangadex 975f3334-8395-4393-84a2-50fcaccbcdc0",
+            "!rss-scrapers vn-news",
+            "https://example.com/feed.xml",
+        ]
+    );
+    assert_eq!(items, ["0b2c3d4e-0000-0000-0000-000000000000", "https://example.com/item"]);
+
+    // Everything is already migrated
+    assert!(migrate::run(&db, false).unwrap().is_empty());
+}
+
+#[test]
+fn dry_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = create(&dir);
+    let before = contents(&db);
+
+    assert_eq!(migrate::run(&db, true).unwrap().len(), 3);
+    assert_eq!(contents(&db), before);
+}
+
+#[test]
+fn missing_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("missing.db");
+
+    assert!(migrate::run(&db, false).is_err());
+    assert!(!db.exists());
+}
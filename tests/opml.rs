@@ -0,0 +1,93 @@
+//! Imports OPML into batch manifests and exports them back.
+
+use std::fs;
+use std::path::Path;
+
+use rss_scrapers::opml::{self, Source};
+
+const SERVE_URL: &str = "http://localhost:8080";
+
+const OPML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head><title>Subscriptions</title></head>
+  <body>
+    <outline text="News" xmlUrl="!rss-scrapers vn-news"/>
+    <outline text="Manga">
+      <outline type="rss" text="Manga" xmlUrl="http://localhost:8080/mangadex/975f3334-8395-4393-84a2-50fcaccbcdc0?format=json"/>
+      <outline type="rss" text="Same manga" xmlUrl="!rss-scrapers mangadex 975f3334-8395-4393-84a2-50fcaccbcdc0"/>
+    </outline>
+    <outline type="rss" text="Pictures" xmlUrl="!rss-scrapers gelbooru a:b"/>
+    <outline type="rss" text="Other pictures" xmlUrl="!rss-scrapers gelbooru a_b"/>
+    <outline type="rss" text="Not a scraper" xmlUrl="https://example.com/feed.xml"/>
+  </body>
+</opml>
+"#;
+
+fn xml_urls(opml: &str) -> Vec<&str> {
+    opml.split("xmlUrl=\"").skip(1).filter_map(|s| s.split('"').next()).collect()
+}
+
+#[test]
+fn import() {
+    let manifest = opml::import(OPML, Path::new("/srv/feeds"), Some(SERVE_URL)).unwrap();
+
+    assert_eq!(
+        manifest,
+        r#"[[feeds]]
+command = ["vn-news"]
+output = "/srv/feeds/vn-news.xml"
+
+[[feeds]]
+command = ["mangadex", "975f3334-8395-4393-84a2-50fcaccbcdc0"]
+output = "/srv/feeds/mangadex-975f3334-8395-4393-84a2-50fcaccbcdc0.xml"
+
+[[feeds]]
+command = ["gelbooru", "a:b"]
+output = "/srv/feeds/gelbooru-a_b.xml"
+
+[[feeds]]
+command = ["gelbooru", "a_b"]
+output = "/srv/feeds/gelbooru-a_b-2.xml"
+"#
+    );
+}
+
+#[test]
+fn invalid_command() {
+    let opml = OPML.replace("!rss-scrapers vn-news", "!rss-scrapers not-a-scraper");
+    assert!(opml::import(&opml, Path::new("/srv/feeds"), None).is_err());
+}
+
+#[test]
+fn round_trip() {
+    let imported = opml::import(OPML, Path::new("/srv/feeds"), Some(SERVE_URL)).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("manifest.toml");
+    fs::write(&path, &imported).unwrap();
+
+    let commands = opml::export(Source::Manifest(&path), None).unwrap();
+    assert_eq!(
+        xml_urls(&commands),
+        [
+            "!rss-scrapers vn-news",
+            "!rss-scrapers mangadex 975f3334-8395-4393-84a2-50fcaccbcdc0",
+            "!rss-scrapers gelbooru a:b",
+            "!rss-scrapers gelbooru a_b",
+        ]
+    );
+    let served = opml::export(Source::Manifest(&path), Some(SERVE_URL)).unwrap();
+    assert_eq!(
+        xml_urls(&served),
+        [
+            "http://localhost:8080/vn-news",
+            "http://localhost:8080/mangadex/975f3334-8395-4393-84a2-50fcaccbcdc0",
+            "http://localhost:8080/gelbooru/a%3Ab",
+            "http://localhost:8080/gelbooru/a_b",
+        ]
+    );
+
+    for opml in [commands, served] {
+        let reimported = opml::import(&opml, Path::new("/srv/feeds"), Some(SERVE_URL)).unwrap();
+        assert_eq!(reimported, imported);
+    }
+}
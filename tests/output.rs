@@ -0,0 +1,44 @@
+//! Renders saved feeds as Atom and JSON Feed and compares them against golden files.
+//!
+//! Set `UPDATE_GOLDEN=1` to rewrite the golden files after an intentional change.
+
+mod common;
+
+use std::collections::HashSet;
+
+use common::{fixture, fixture_str, golden};
+use rss_scrapers::output::{self, Format};
+use rss_scrapers::{Feed, mangadex, vn_news};
+
+// Bare chapter ids as guids, extras for every chapter and an etag
+fn mangadex() -> Feed {
+    let series = "975f3334-8395-4393-84a2-50fcaccbcdc0";
+    let blocked = HashSet::from(["blocked0-0000-0000-0000-000000000000".to_string()]);
+
+    let feed = mangadex::parse(series, &fixture("mangadex/manga.json"), &blocked, |_| {
+        Ok(fixture("mangadex/feed.json"))
+    })
+    .unwrap();
+
+    Feed { etag: Some("\"abc\"".to_string()), ..feed }
+}
+
+#[test]
+fn atom() {
+    golden("mangadex.atom", &output::render(mangadex(), Format::Atom));
+}
+
+#[test]
+fn json() {
+    golden("mangadex.json", &output::render(mangadex(), Format::Json));
+}
+
+// Urls as guids and no dates, so every entry falls back to the build date
+#[test]
+fn atom_without_dates() {
+    let mut channel = vn_news::parse(&fixture_str("vn_news/forum.html")).unwrap();
+    channel.set_last_build_date("Sat, 10 Jan 2026 12:00:00 +0000".to_string());
+
+    let feed = Feed { channel, ..Feed::default() };
+    golden("vn_news.atom", &output::render(feed, Format::Atom));
+}
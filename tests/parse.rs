@@ -0,0 +1,141 @@
+//! Runs each scraper's parser over saved responses and compares the feeds against golden files.
+//!
+//! Set `UPDATE_GOLDEN=1` to rewrite the golden files after an intentional change.
+
+mod common;
+
+use std::collections::{HashMap, HashSet};
+
+use common::{fixture, fixture_str};
+use rss::Channel;
+use rss_scrapers::{Category, ao3, gelbooru, jnovel, mangadex, royalroad, tfgames, vn_news};
+
+fn golden(name: &str, channel: &Channel) {
+    let actual = String::from_utf8(channel.pretty_write_to(Vec::new(), b' ', 2).unwrap()).unwrap();
+    common::golden(&format!("{name}.xml"), &actual);
+}
+
+#[test]
+fn ao3() {
+    let channel = ao3::parse("1234", &fixture_str("ao3/navigate.html")).unwrap();
+    golden("ao3", &channel);
+}
+
+#[test]
+fn ao3_missing_title() {
+    assert!(ao3::parse("1234", "<html><body></body></html>").is_err());
+}
+
+#[test]
+fn gelbooru() {
+    let query = vec!["some_series".to_string()];
+    let blacklist = HashSet::from(["blacklisted_tag".to_string()]);
+
+    let feed = gelbooru::parse(&query, &blacklist, &fixture("gelbooru/index.json"), |tags| {
+        assert!(!tags.contains(&"blacklisted_tag"));
+        Ok(HashMap::from([
+            ("1girl".to_string(), 0),
+            ("solo".to_string(), 0),
+            ("some_artist".to_string(), 1),
+            ("some_series".to_string(), 3),
+            ("some_character".to_string(), 4),
+        ]))
+    })
+    .unwrap();
+
+    assert_eq!(feed.extras["9001"]["md5"], "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    golden("gelbooru", &feed.channel);
+}
+
+#[test]
+fn gelbooru_tags() {
+    let tags = ["1girl", "some_artist", "some_character"];
+    let mut types = gelbooru::parse_tags(&tags, &fixture("gelbooru/tags.json")).unwrap();
+    types.sort();
+
+    assert_eq!(
+        types,
+        vec![
+            ("1girl".to_string(), 0),
+            ("Some_Artist".to_string(), 1),
+            ("some_artist".to_string(), 1),
+            ("some_character".to_string(), 4),
+        ]
+    );
+}
+
+#[test]
+fn gelbooru_tags_missing() {
+    let tags = ["1girl", "some_artist", "some_character", "not_in_response"];
+    let e = gelbooru::parse_tags(&tags, &fixture("gelbooru/tags.json")).unwrap_err();
+    assert_eq!(e.category(), Category::Parse);
+}
+
+#[test]
+fn jnovel() {
+    let channel = jnovel::parse("a-light-novel", &fixture("jnovel/series.json"), |id| {
+        assert_eq!(id, "5c1f2a3b4d5e6f7a8b9c0d1e");
+        Ok(fixture("jnovel/events.json"))
+    })
+    .unwrap();
+
+    golden("jnovel", &channel);
+}
+
+#[test]
+fn mangadex() {
+    let series = "975f3334-8395-4393-84a2-50fcaccbcdc0";
+    let blocked = HashSet::from(["blocked0-0000-0000-0000-000000000000".to_string()]);
+
+    let feed = mangadex::parse(series, &fixture("mangadex/manga.json"), &blocked, |offset| {
+        assert_eq!(offset, 0);
+        Ok(fixture("mangadex/feed.json"))
+    })
+    .unwrap();
+
+    assert_eq!(feed.extras["c0000000-0000-0000-0000-000000000004"]["chapter"], "12");
+    golden("mangadex", &feed.channel);
+}
+
+#[test]
+fn mangadex_not_ok() {
+    let info = br#"{"result": "error", "data": {"attributes": {"title": {}, "description": {}}}}"#;
+    let result = mangadex::parse("abc", info, &HashSet::new(), |_| unreachable!());
+    assert!(result.is_err());
+}
+
+#[test]
+fn mangadex_bad_date() {
+    let series = "975f3334-8395-4393-84a2-50fcaccbcdc0";
+    let info = fixture("mangadex/manga.json");
+    let result =
+        mangadex::parse(series, &info, &HashSet::new(), |_| Ok(fixture("mangadex/bad_date.json")));
+    assert_eq!(result.unwrap_err().category(), Category::Parse);
+}
+
+#[test]
+fn royalroad() {
+    let pages = ["trending", "rising-stars", "weekly-popular"]
+        .map(|page| fixture_str(&format!("royalroad/{page}.html")));
+
+    let channel = royalroad::parse(&pages).unwrap();
+    golden("royalroad", &channel);
+}
+
+#[test]
+fn tfgames() {
+    let channel = tfgames::parse("1234", &fixture_str("tfgames/viewgame.html")).unwrap();
+    golden("tfgames", &channel);
+}
+
+#[test]
+fn vn_news() {
+    let channel = vn_news::parse(&fixture_str("vn_news/forum.html")).unwrap();
+    golden("vn_news", &channel);
+}
+
+#[test]
+fn vn_news_missing_href() {
+    let e = vn_news::parse(&fixture_str("vn_news/missing_href.html")).unwrap_err();
+    assert_eq!(e.category(), Category::Parse);
+}
@@ -0,0 +1,81 @@
+//! Checks that the validator flags the problems scrapers have shipped before.
+
+use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
+use rss_scrapers::validate;
+
+fn item(title: &str, guid: &str) -> rss::Item {
+    ItemBuilder::default()
+        .title(Some(title.to_string()))
+        .link(Some(format!("https://example.com/{guid}")))
+        .guid(Some(GuidBuilder::default().value(guid.to_string()).permalink(false).build()))
+        .build()
+}
+
+#[test]
+fn valid() {
+    let channel = ChannelBuilder::default()
+        .title("Feed")
+        .link("https://example.com")
+        .items(vec![item("One", "1"), item("Two", "2")])
+        .build();
+
+    assert_eq!(validate::check(&channel), Vec::new());
+}
+
+#[test]
+fn duplicate_guids() {
+    let channel = ChannelBuilder::default()
+        .title("Feed")
+        .link("https://example.com")
+        .items(vec![item("One", "1"), item("Two", "2"), item("One again", "1")])
+        .build();
+
+    let violations = validate::check(&channel);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].item, Some(2));
+}
+
+#[test]
+fn missing_fields() {
+    let mut empty_title = item(" ", "1");
+    empty_title.set_pub_date("yesterday".to_string());
+    let mut no_link = item("Two", "2");
+    no_link.set_link(None);
+
+    let channel = ChannelBuilder::default()
+        .title("")
+        .link("https://example.com")
+        .items(vec![empty_title, no_link])
+        .build();
+
+    let messages: Vec<_> = validate::check(&channel).iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        [
+            "channel: missing title",
+            "item 0: missing title",
+            "item 0: invalid pubDate \"yesterday\"",
+            "item 1: missing link",
+        ]
+    );
+}
+
+#[test]
+fn guid_in_place_of_link() {
+    let guid = |value: &str| Some(GuidBuilder::default().value(value.to_string()).build());
+    let mut url_guid = item("One", "1");
+    url_guid.set_link(None);
+    url_guid.set_guid(guid("https://example.com/1"));
+    let mut bare_guid = item("Two", "2");
+    bare_guid.set_link(None);
+    bare_guid.set_guid(guid("9001"));
+
+    let channel = ChannelBuilder::default()
+        .title("Feed")
+        .link("https://example.com")
+        .items(vec![url_guid, bare_guid])
+        .build();
+
+    let messages: Vec<_> = validate::check(&channel).iter().map(ToString::to_string).collect();
+    assert_eq!(messages, ["item 1: missing link"]);
+}